@@ -0,0 +1,64 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use redis_resp::{frame_len, Parser, Tape};
+
+// reply of an `MGET` with `count` keys where every fifth key is missing
+fn mget_reply(count: usize) -> Vec<u8> {
+    let mut data = format!("*{}\r\n", count).into_bytes();
+    for i in 0..count {
+        if i % 5 == 0 {
+            data.extend_from_slice(b"$-1\r\n");
+        } else {
+            let value = format!("value:{}", i);
+            data.extend_from_slice(format!("${}\r\n{}\r\n", value.len(), value).as_bytes());
+        }
+    }
+    data
+}
+
+// reply of an `HGETALL`-like call in RESP2 style, `count` arrays of a field and a value
+fn nested_reply(count: usize) -> Vec<u8> {
+    let mut data = format!("*{}\r\n", count).into_bytes();
+    for i in 0..count {
+        data.extend_from_slice(format!("*2\r\n+field:{}\r\n:{}\r\n", i, i).as_bytes());
+    }
+    data
+}
+
+fn bench_mget(c: &mut Criterion) {
+    for count in [100, 10_000] {
+        let data = mget_reply(count);
+        let mut group = c.benchmark_group(format!("mget_{}", count));
+        group.throughput(Throughput::Bytes(data.len() as u64));
+
+        group.bench_function("parser", |b| {
+            b.iter(|| Parser::new_from_bytes(black_box(&data)).parse().unwrap())
+        });
+        group.bench_function("tape", |b| {
+            b.iter(|| Tape::parse(black_box(&data)).unwrap())
+        });
+        group.bench_function("frame_len", |b| {
+            b.iter(|| frame_len(black_box(&data)).unwrap())
+        });
+        group.finish();
+    }
+}
+
+fn bench_nested(c: &mut Criterion) {
+    let data = nested_reply(10_000);
+    let mut group = c.benchmark_group("nested_10000");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+
+    group.bench_function("parser", |b| {
+        b.iter(|| Parser::new_from_bytes(black_box(&data)).parse().unwrap())
+    });
+    group.bench_function("tape", |b| {
+        b.iter(|| Tape::parse(black_box(&data)).unwrap())
+    });
+    group.bench_function("frame_len", |b| {
+        b.iter(|| frame_len(black_box(&data)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_mget, bench_nested);
+criterion_main!(benches);
@@ -0,0 +1,513 @@
+use std::fmt::Display;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::decoder::FrameBuffer;
+use crate::lexer::find_newline;
+use crate::{Command, Lexer, Parser, ParserOptions, RespErrorType};
+
+const READ_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub enum AofError {
+    Io(std::io::Error),
+    /// The file ends inside the command or annotation that starts at `offset`.
+    Truncated {
+        offset: u64,
+    },
+    /// The command starting at `offset` is invalid, `position` is where the parser gave up
+    /// if it could tell.
+    Parse {
+        offset: u64,
+        position: Option<u64>,
+        error_type: RespErrorType,
+    },
+    /// A valid frame that is not an array of bulk strings.
+    NotACommand {
+        offset: u64,
+    },
+    /// The file starts with an RDB snapshot (`aof-use-rdb-preamble`) instead of commands.
+    RdbPreamble,
+    /// Line of the manifest that could not be parsed, starting at 1.
+    Manifest {
+        line: usize,
+    },
+}
+
+impl AofError {
+    /// Where the last valid command ends, the file can be truncated to this length.
+    pub fn valid_len(&self) -> Option<u64> {
+        match self {
+            AofError::Truncated { offset }
+            | AofError::Parse { offset, .. }
+            | AofError::NotACommand { offset } => Some(*offset),
+            AofError::Io(_) | AofError::RdbPreamble | AofError::Manifest { .. } => None,
+        }
+    }
+}
+
+impl Display for AofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            AofError::Io(error) => write!(f, "{}", error),
+            AofError::Truncated { offset } => {
+                write!(f, "truncated command at offset {}", offset)
+            }
+            AofError::Parse {
+                offset,
+                position,
+                error_type,
+            } => {
+                write!(f, "{:?} in the command at offset {}", error_type, offset)?;
+                match position {
+                    Some(position) => write!(f, " (at offset {})", position),
+                    None => Ok(()),
+                }
+            }
+            AofError::NotACommand { offset } => {
+                write!(f, "frame at offset {} is not a command", offset)
+            }
+            AofError::RdbPreamble => write!(f, "file starts with an RDB preamble"),
+            AofError::Manifest { line } => write!(f, "invalid manifest line {}", line),
+        }
+    }
+}
+
+impl std::error::Error for AofError {}
+
+impl From<std::io::Error> for AofError {
+    fn from(error: std::io::Error) -> Self {
+        AofError::Io(error)
+    }
+}
+
+/// A command read from an AOF, `offset` and `len` locate its bytes in the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AofEntry {
+    pub offset: u64,
+    pub len: usize,
+    pub command: Command,
+}
+
+/// Iterates the commands of an AOF without loading the whole file. Annotations like the
+/// `#TS:<unix time>` lines of Redis 7 are skipped. The iterator ends after the first error.
+pub struct AofReader<R> {
+    reader: R,
+    buffer: FrameBuffer,
+    // file offset of the first pending byte
+    offset: u64,
+    eof: bool,
+    done: bool,
+}
+
+impl AofReader<std::fs::File> {
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<AofReader<std::fs::File>> {
+        Ok(AofReader::new(std::fs::File::open(path)?))
+    }
+}
+
+impl<R: Read> AofReader<R> {
+    pub fn new(reader: R) -> AofReader<R> {
+        AofReader {
+            reader,
+            buffer: FrameBuffer::new(ParserOptions::default(), READ_SIZE),
+            offset: 0,
+            eof: false,
+            done: false,
+        }
+    }
+
+    pub fn set_parser_options(&mut self, options: ParserOptions) {
+        self.buffer.set_options(options);
+    }
+
+    /// Where the next command starts, after the last one that was read successfully.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn advance(&mut self, len: usize) {
+        self.buffer.consume(len);
+        self.offset += len as u64;
+    }
+
+    fn read_entry(&mut self) -> Result<Option<AofEntry>, AofError> {
+        loop {
+            let data = self.buffer.pending();
+
+            if self.offset == 0 && data.starts_with(b"REDIS") {
+                return Err(AofError::RdbPreamble);
+            }
+
+            if data.first() == Some(&b'#') {
+                if let Some(end) = find_newline(data) {
+                    self.advance(end + 2);
+                    continue;
+                }
+            } else if !data.is_empty() {
+                let offset = self.offset;
+                let options = self.buffer.options();
+                let parse_error = |error: crate::ParseError| AofError::Parse {
+                    offset,
+                    position: error.token().map(|token| offset + token.start as u64),
+                    error_type: error.error_type(),
+                };
+
+                if let Some(len) = self.buffer.frame_len().map_err(parse_error)? {
+                    let frame = &self.buffer.pending()[..len];
+                    let command = Parser::new_with_options(Lexer::new(frame), options)
+                        .parse()
+                        .map_err(parse_error)?
+                        .to_owned()
+                        .try_into()
+                        .map_err(|_| AofError::NotACommand { offset })?;
+
+                    self.advance(len);
+                    return Ok(Some(AofEntry {
+                        offset,
+                        len,
+                        command,
+                    }));
+                }
+            }
+
+            if self.eof {
+                return match self.buffer.pending().is_empty() {
+                    true => Ok(None),
+                    false => Err(AofError::Truncated {
+                        offset: self.offset,
+                    }),
+                };
+            }
+
+            self.eof = self.buffer.read_from(&mut self.reader)? == 0;
+        }
+    }
+}
+
+impl<R: Read> Iterator for AofReader<R> {
+    type Item = Result<AofEntry, AofError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.read_entry().transpose();
+        self.done = !matches!(result, Some(Ok(_)));
+        result
+    }
+}
+
+/// Writes commands the way Redis appends them, a `SELECT` is added whenever the database
+/// changes.
+pub struct AofWriter<W> {
+    writer: W,
+    database: Option<u32>,
+    output: Vec<u8>,
+}
+
+impl<W: Write> AofWriter<W> {
+    pub fn new(writer: W) -> AofWriter<W> {
+        AofWriter {
+            writer,
+            database: None,
+            output: Vec::new(),
+        }
+    }
+
+    pub fn write(&mut self, database: u32, command: &Command) -> std::io::Result<()> {
+        self.output.clear();
+
+        if self.database != Some(database) {
+            Command::new("SELECT")
+                .arg(database.to_string())
+                .write_to_vec(&mut self.output);
+            self.database = Some(database);
+        }
+        command.write_to_vec(&mut self.output);
+
+        self.writer.write_all(&self.output)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AofFileType {
+    Base,
+    History,
+    Incremental,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub seq: u64,
+    pub file_type: AofFileType,
+}
+
+/// The `appendonly.aof.manifest` of a Redis 7 multi part AOF, listing the base file and the
+/// incremental files written after it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn read(path: impl AsRef<Path>) -> Result<Manifest, AofError> {
+        Manifest::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(input: &str) -> Result<Manifest, AofError> {
+        let mut entries = Vec::new();
+
+        for (index, line) in input.lines().enumerate() {
+            let error = || AofError::Manifest { line: index + 1 };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = split_fields(line).ok_or_else(error)?;
+            let (mut name, mut seq, mut file_type) = (None, None, None);
+            for pair in fields.chunks(2) {
+                let [key, value] = pair else {
+                    return Err(error());
+                };
+
+                match key.as_str() {
+                    "file" => name = Some(value.clone()),
+                    "seq" => seq = Some(value.parse().map_err(|_| error())?),
+                    "type" => {
+                        file_type = Some(match value.as_str() {
+                            "b" => AofFileType::Base,
+                            "h" => AofFileType::History,
+                            "i" => AofFileType::Incremental,
+                            _ => return Err(error()),
+                        })
+                    }
+                    // newer versions may add keys
+                    _ => (),
+                }
+            }
+
+            entries.push(ManifestEntry {
+                name: name.ok_or_else(error)?,
+                seq: seq.ok_or_else(error)?,
+                file_type: file_type.ok_or_else(error)?,
+            });
+        }
+
+        Ok(Manifest { entries })
+    }
+
+    pub fn base(&self) -> Option<&ManifestEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.file_type == AofFileType::Base)
+    }
+
+    /// The base file followed by the incremental files in sequence order, history files are
+    /// left out since they are about to be deleted.
+    pub fn load_order(&self) -> Vec<&ManifestEntry> {
+        let mut incremental: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.file_type == AofFileType::Incremental)
+            .collect();
+        incremental.sort_by_key(|entry| entry.seq);
+
+        self.base().into_iter().chain(incremental).collect()
+    }
+}
+
+// Splits on spaces, names with spaces are written in double quotes with `\` escapes.
+fn split_fields(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            return Some(fields);
+        };
+
+        let mut field = String::new();
+        if first == '"' {
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => field.push(chars.next()?),
+                    c => field.push(c),
+                }
+            }
+        } else {
+            field.push(first);
+            while let Some(c) = chars.next_if(|c| !c.is_ascii_whitespace()) {
+                field.push(c);
+            }
+        }
+        fields.push(field);
+    }
+}
+
+#[cfg(test)]
+const AOF: &[u8] = b"*2\r\n$6\r\nSELECT\r\n$1\r\n0\r\n#TS:1700000000\r\n\
+    *3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n*2\r\n$4\r\nINCR\r\n$1\r\nn\r\n";
+
+#[test]
+fn aof_reader_offsets() {
+    let entries: Vec<AofEntry> = AofReader::new(AOF).map(|x| x.unwrap()).collect();
+
+    assert_eq!(entries.len(), 3);
+    assert_eq!(
+        entries[1].command,
+        Command::new("SET").arg("key").arg("value")
+    );
+    assert_eq!(entries[1].offset, 39);
+    assert_eq!(
+        &AOF[entries[2].offset as usize..][..entries[2].len],
+        b"*2\r\n$4\r\nINCR\r\n$1\r\nn\r\n"
+    );
+}
+
+#[test]
+fn aof_reader_errors() {
+    let truncated = &AOF[..AOF.len() - 3];
+    let results: Vec<_> = AofReader::new(truncated).collect();
+
+    assert_eq!(results.len(), 3);
+    assert!(matches!(
+        results[2],
+        Err(AofError::Truncated { offset: 72 })
+    ));
+
+    let mut invalid = AOF[..39].to_vec();
+    invalid.extend_from_slice(b"*2\r\n$3\r\nGET\r\n#x\r\n");
+    match AofReader::new(invalid.as_slice()).nth(1) {
+        Some(Err(error @ AofError::Parse { .. })) => {
+            assert_eq!(error.valid_len(), Some(39));
+            assert!(matches!(
+                error,
+                AofError::Parse {
+                    position: Some(53),
+                    error_type: RespErrorType::InvalidData,
+                    ..
+                }
+            ));
+        }
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+
+    // rejected by the scanner before the frame is complete
+    let mut invalid = AOF[..39].to_vec();
+    invalid.extend_from_slice(b"*2\r\n.\r\n$3\r\nGET\r\n");
+    assert!(matches!(
+        AofReader::new(invalid.as_slice()).nth(1),
+        Some(Err(AofError::Parse {
+            offset: 39,
+            position: Some(43),
+            error_type: RespErrorType::InvalidStart,
+        }))
+    ));
+
+    assert!(matches!(
+        AofReader::new(&b":1\r\n"[..]).next(),
+        Some(Err(AofError::NotACommand { offset: 0 }))
+    ));
+    assert!(matches!(
+        AofReader::new(&b"REDIS0011\xfa"[..]).next(),
+        Some(Err(AofError::RdbPreamble))
+    ));
+}
+
+#[test]
+fn aof_reader_large_file() {
+    let mut data = Vec::new();
+    let mut writer = AofWriter::new(&mut data);
+    for i in 0..20000 {
+        let value = "x".repeat(i % 100);
+        writer
+            .write(0, &Command::new("SET").arg(i.to_string()).arg(value))
+            .unwrap();
+    }
+
+    let entries: Vec<AofEntry> = AofReader::new(data.as_slice())
+        .map(|x| x.unwrap())
+        .collect();
+    assert_eq!(entries.len(), 20001);
+    let last = entries.last().unwrap();
+    assert_eq!(last.offset as usize + last.len, data.len());
+}
+
+#[test]
+fn aof_writer_selects_database() {
+    let mut writer = AofWriter::new(Vec::new());
+    writer
+        .write(0, &Command::new("SET").arg("key").arg("value"))
+        .unwrap();
+    writer.write(0, &Command::new("INCR").arg("n")).unwrap();
+
+    let output = writer.into_inner();
+    // the same commands without the annotation
+    assert_eq!(output, [&AOF[..23], &AOF[39..]].concat());
+
+    let commands: Vec<_> = AofReader::new(output.as_slice())
+        .map(|x| x.unwrap().command)
+        .collect();
+    assert_eq!(
+        commands,
+        vec![
+            Command::new("SELECT").arg("0"),
+            Command::new("SET").arg("key").arg("value"),
+            Command::new("INCR").arg("n"),
+        ]
+    );
+
+    let mut writer = AofWriter::new(Vec::new());
+    writer.write(1, &Command::new("PING")).unwrap();
+    writer.write(2, &Command::new("PING")).unwrap();
+    assert_eq!(AofReader::new(writer.into_inner().as_slice()).count(), 4);
+}
+
+#[test]
+fn aof_manifest() {
+    let manifest = Manifest::parse(
+        "file appendonly.aof.2.incr.aof seq 2 type i\n\
+         file appendonly.aof.1.base.rdb seq 1 type b\n\
+         file appendonly.aof.1.incr.aof seq 1 type i\n\
+         file \"old file.aof\" seq 1 type h\n",
+    )
+    .unwrap();
+
+    let names: Vec<&str> = manifest
+        .load_order()
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "appendonly.aof.1.base.rdb",
+            "appendonly.aof.1.incr.aof",
+            "appendonly.aof.2.incr.aof"
+        ]
+    );
+    assert_eq!(manifest.entries[3].name, "old file.aof");
+
+    assert!(matches!(
+        Manifest::parse("file a seq 1 type b\nfile b seq x type i\n"),
+        Err(AofError::Manifest { line: 2 })
+    ));
+    assert!(matches!(
+        Manifest::parse("file a seq 1\n"),
+        Err(AofError::Manifest { line: 1 })
+    ));
+}
@@ -0,0 +1,411 @@
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::AbortHandle;
+
+use crate::decoder::{DecodeError, FrameBuffer};
+use crate::pool::BufferPool;
+use crate::{Command, ParserOptions, RespType};
+
+const READ_SIZE: usize = 8 * 1024;
+
+type Reply = oneshot::Sender<Result<RespType, DecodeError>>;
+
+// Replies waiting for a frame, in request order. Once the reader stops, `closed` keeps the
+// error so requests the writer picks up later fail as well instead of waiting forever.
+#[derive(Default)]
+struct Pending {
+    replies: VecDeque<Reply>,
+    closed: Option<DecodeError>,
+}
+
+type SharedPending = Arc<Mutex<Pending>>;
+
+struct Request {
+    data: Vec<u8>,
+    reply: Reply,
+}
+
+/// Async client connection that can be cloned and shared between tasks. Requests are written
+/// in the order they are made and replies are matched to them by that order, RESP3 push
+/// frames go to the receiver returned by `new` instead. The socket is closed when the last
+/// clone is dropped or `close` is called.
+#[derive(Clone)]
+pub struct AsyncConnection {
+    requests: mpsc::UnboundedSender<Request>,
+    pending: SharedPending,
+    reader: AbortHandle,
+    writer: AbortHandle,
+}
+
+impl AsyncConnection {
+    /// Spawns the reader and writer tasks on the current tokio runtime.
+    pub fn new<S>(stream: S) -> (AsyncConnection, mpsc::UnboundedReceiver<RespType>)
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        AsyncConnection::new_with_options(stream, ParserOptions::default())
+    }
+
+    pub fn new_with_options<S>(
+        stream: S,
+        options: ParserOptions,
+    ) -> (AsyncConnection, mpsc::UnboundedReceiver<RespType>)
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        let (requests, request_receiver) = mpsc::unbounded_channel();
+        let (pushes, push_receiver) = mpsc::unbounded_channel();
+        let pending = SharedPending::default();
+
+        let reader =
+            tokio::spawn(read_replies(reader, pending.clone(), pushes, options)).abort_handle();
+        let writer = tokio::spawn(write_requests(
+            writer,
+            request_receiver,
+            pending.clone(),
+            reader.clone(),
+        ))
+        .abort_handle();
+
+        let connection = AsyncConnection {
+            requests,
+            pending,
+            reader,
+            writer,
+        };
+        (connection, push_receiver)
+    }
+
+    pub async fn connect_tcp(
+        address: impl ToSocketAddrs,
+    ) -> std::io::Result<(AsyncConnection, mpsc::UnboundedReceiver<RespType>)> {
+        let stream = TcpStream::connect(address).await?;
+        stream.set_nodelay(true)?;
+        Ok(AsyncConnection::new(stream))
+    }
+
+    #[cfg(unix)]
+    pub async fn connect_unix(
+        path: impl AsRef<Path>,
+    ) -> std::io::Result<(AsyncConnection, mpsc::UnboundedReceiver<RespType>)> {
+        let stream = tokio::net::UnixStream::connect(path).await?;
+        Ok(AsyncConnection::new(stream))
+    }
+
+    pub async fn request(&self, command: &Command) -> Result<RespType, DecodeError> {
+        let receiver = self.enqueue(command)?;
+        receive(receiver).await
+    }
+
+    /// Queues all commands before waiting for the first reply, so they share round trips.
+    pub async fn pipeline(&self, commands: &[Command]) -> Result<Vec<RespType>, DecodeError> {
+        let receivers = commands
+            .iter()
+            .map(|command| self.enqueue(command))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut replies = Vec::with_capacity(receivers.len());
+        for receiver in receivers {
+            replies.push(receive(receiver).await?);
+        }
+
+        Ok(replies)
+    }
+
+    /// Whether the reader or writer task stopped, e.g. because the socket was closed.
+    pub fn is_closed(&self) -> bool {
+        self.requests.is_closed() || self.pending.lock().unwrap().closed.is_some()
+    }
+
+    /// Closes the socket for all clones, waiting requests fail with `NotConnected`.
+    pub fn close(&self) {
+        fail_pending(&self.pending, closed());
+        self.writer.abort();
+        self.reader.abort();
+    }
+
+    fn enqueue(
+        &self,
+        command: &Command,
+    ) -> Result<oneshot::Receiver<Result<RespType, DecodeError>>, DecodeError> {
+        let mut data = Vec::new();
+        command.write_to_vec(&mut data);
+
+        let (reply, receiver) = oneshot::channel();
+        self.requests
+            .send(Request { data, reply })
+            .map_err(|_| closed())?;
+
+        Ok(receiver)
+    }
+}
+
+async fn receive(
+    receiver: oneshot::Receiver<Result<RespType, DecodeError>>,
+) -> Result<RespType, DecodeError> {
+    receiver.await.unwrap_or_else(|_| Err(closed()))
+}
+
+fn closed() -> DecodeError {
+    DecodeError::Io(ErrorKind::NotConnected.into())
+}
+
+// every waiting request gets its own copy of the error that ended the connection
+fn copy_error(error: &DecodeError) -> DecodeError {
+    match error {
+        DecodeError::Io(error) => {
+            DecodeError::Io(std::io::Error::new(error.kind(), error.to_string()))
+        }
+        DecodeError::Parse(error_type) => DecodeError::Parse(*error_type),
+    }
+}
+
+fn fail_pending(pending: &SharedPending, error: DecodeError) {
+    let mut pending = pending.lock().unwrap();
+    for reply in pending.replies.drain(..) {
+        let _ = reply.send(Err(copy_error(&error)));
+    }
+    pending.closed.get_or_insert(error);
+}
+
+// The reader owns the other half of the stream, it is stopped as well so the socket is
+// closed once the writer is done.
+async fn write_requests<W: AsyncWrite>(
+    writer: W,
+    requests: mpsc::UnboundedReceiver<Request>,
+    pending: SharedPending,
+    reader: AbortHandle,
+) {
+    tokio::pin!(writer);
+
+    if write_all_requests(&mut writer, requests, &pending).await {
+        // all handles are gone
+        let _ = writer.shutdown().await;
+    }
+    reader.abort();
+}
+
+// Returns false if the connection failed.
+async fn write_all_requests<W: AsyncWrite>(
+    writer: &mut std::pin::Pin<&mut W>,
+    mut requests: mpsc::UnboundedReceiver<Request>,
+    pending: &SharedPending,
+) -> bool {
+    let mut output = Vec::new();
+
+    while let Some(request) = requests.recv().await {
+        output.clear();
+
+        // write everything that is queued at once
+        let mut next = Some(request);
+        while let Some(request) = next {
+            let mut pending = pending.lock().unwrap();
+            if let Some(error) = &pending.closed {
+                let _ = request.reply.send(Err(copy_error(error)));
+                return false;
+            }
+
+            output.extend_from_slice(&request.data);
+            pending.replies.push_back(request.reply);
+            next = requests.try_recv().ok();
+        }
+
+        if let Err(error) = writer.write_all(&output).await {
+            fail_pending(pending, DecodeError::Io(error));
+            return false;
+        }
+    }
+
+    true
+}
+
+async fn read_replies<R: AsyncRead>(
+    reader: R,
+    pending: SharedPending,
+    pushes: mpsc::UnboundedSender<RespType>,
+    options: ParserOptions,
+) {
+    tokio::pin!(reader);
+    let mut buffer = FrameBuffer::new(options, READ_SIZE);
+    let mut nodes = Vec::new();
+    // only frames nobody waits for anymore come back
+    let mut pool = BufferPool::default();
+
+    let error = loop {
+        match buffer.decode(&mut nodes, &mut pool) {
+            Ok(Some(RespType::Push(data))) => {
+                if let Err(error) = pushes.send(RespType::Push(data)) {
+                    pool.recycle(error.0);
+                }
+                continue;
+            }
+            Ok(Some(item)) => match pending.lock().unwrap().replies.pop_front() {
+                Some(reply) => {
+                    if let Err(Ok(item)) = reply.send(Ok(item)) {
+                        pool.recycle(item);
+                    }
+                    continue;
+                }
+                None => break DecodeError::Io(ErrorKind::InvalidData.into()),
+            },
+            Ok(None) => (),
+            Err(error) => break error,
+        }
+
+        match reader.read(buffer.spare()).await {
+            Ok(0) => break DecodeError::Io(ErrorKind::UnexpectedEof.into()),
+            Ok(read) => buffer.filled(read),
+            Err(error) => break DecodeError::Io(error),
+        }
+    };
+
+    fail_pending(&pending, error);
+}
+
+#[cfg(test)]
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn async_connection_concurrent_requests() {
+    let address = crate::connection::spawn_mock_server();
+
+    runtime().block_on(async {
+        let (connection, _) = AsyncConnection::connect_tcp(address).await.unwrap();
+
+        let tasks: Vec<_> = (0..50)
+            .map(|i| {
+                let connection = connection.clone();
+                tokio::spawn(async move {
+                    let reply = connection
+                        .request(&Command::new("ECHO").arg(i.to_string()))
+                        .await
+                        .unwrap();
+                    assert_eq!(reply, RespType::BulkString(i.to_string().into_bytes()));
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+    });
+}
+
+#[test]
+fn async_connection_pipeline_and_pushes() {
+    let address = crate::connection::spawn_mock_server();
+
+    runtime().block_on(async {
+        let (connection, mut pushes) = AsyncConnection::connect_tcp(address).await.unwrap();
+
+        let replies = connection
+            .pipeline(&[
+                Command::new("PING"),
+                Command::new("PUSH").arg("hello"),
+                Command::new("ECHO").arg("after"),
+            ])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            replies,
+            vec![
+                RespType::SimpleString(b"PONG".to_vec()),
+                RespType::SimpleString(b"OK".to_vec()),
+                RespType::BulkString(b"after".to_vec()),
+            ]
+        );
+        assert_eq!(
+            pushes.recv().await.unwrap(),
+            RespType::Push(vec![
+                RespType::BulkString(b"message".to_vec()),
+                RespType::BulkString(b"hello".to_vec()),
+            ])
+        );
+    });
+}
+
+#[test]
+fn async_connection_closed() {
+    let address = crate::connection::spawn_mock_server();
+
+    runtime().block_on(async {
+        let (connection, _) = AsyncConnection::connect_tcp(address).await.unwrap();
+        connection.request(&Command::new("QUIT")).await.unwrap();
+
+        assert!(matches!(
+            connection.request(&Command::new("PING")).await,
+            Err(DecodeError::Io(_))
+        ));
+        assert!(connection.is_closed());
+    });
+}
+
+#[test]
+fn async_connection_large_reply() {
+    let address = crate::connection::spawn_mock_server();
+
+    runtime().block_on(async {
+        let (connection, _) = AsyncConnection::connect_tcp(address).await.unwrap();
+        let value = "x".repeat(300_000);
+
+        let reply = connection
+            .request(&Command::new("ECHO").arg(value.as_str()))
+            .await
+            .unwrap();
+        assert_eq!(reply, RespType::BulkString(value.into_bytes()));
+    });
+}
+
+#[cfg(test)]
+pub(crate) async fn wait_for(count: &std::sync::atomic::AtomicUsize, expected: usize) {
+    for _ in 0..200 {
+        if count.load(std::sync::atomic::Ordering::SeqCst) == expected {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("expected {} closed connections", expected);
+}
+
+#[test]
+fn async_connection_closes_socket() {
+    let (address, closed) = crate::connection::spawn_counting_mock_server();
+
+    runtime().block_on(async {
+        let (connection, pushes) = AsyncConnection::connect_tcp(address).await.unwrap();
+        connection.request(&Command::new("PING")).await.unwrap();
+        let clone = connection.clone();
+        drop(connection);
+        assert!(clone.request(&Command::new("PING")).await.is_ok());
+
+        drop(clone);
+        drop(pushes);
+        wait_for(&closed, 1).await;
+
+        // an explicit close also ends the requests of the other clones
+        let (connection, _) = AsyncConnection::connect_tcp(address).await.unwrap();
+        let clone = connection.clone();
+        connection.request(&Command::new("PING")).await.unwrap();
+        clone.close();
+        assert!(connection.is_closed());
+        assert!(matches!(
+            connection.request(&Command::new("PING")).await,
+            Err(DecodeError::Io(_))
+        ));
+        wait_for(&closed, 2).await;
+    });
+}
@@ -0,0 +1,143 @@
+//! Validates an append-only file command by command, like `redis-check-aof`.
+//!
+//! Usage: `check-aof [--fix [--force]] <file>`
+//!
+//! Prints how often each command occurs and how many bytes it takes. When the file is
+//! invalid the offset and parser error of the first bad command are printed and `--fix`
+//! truncates the file to the last valid command. A file that just ends inside a command is
+//! truncated right away, an invalid command in the middle of the file would discard
+//! everything after it, so that asks for confirmation first unless `--force` is given.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::process::ExitCode;
+
+use redis_resp::aof::AofError;
+use redis_resp::AofReader;
+
+#[derive(Default)]
+struct Stats {
+    count: u64,
+    bytes: u64,
+}
+
+fn usage() -> ExitCode {
+    eprintln!("usage: check-aof [--fix [--force]] <file>");
+    ExitCode::from(2)
+}
+
+fn confirm() -> bool {
+    print!("Continue? [y/N]: ");
+    let _ = std::io::stdout().flush();
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok() && answer.trim().eq_ignore_ascii_case("y")
+}
+
+fn main() -> ExitCode {
+    let mut fix = false;
+    let mut force = false;
+    let mut path = None;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--fix" => fix = true,
+            "--force" => force = true,
+            "-h" | "--help" => return usage(),
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => return usage(),
+        }
+    }
+    let Some(path) = path else {
+        return usage();
+    };
+    if force && !fix {
+        return usage();
+    }
+
+    let reader = match AofReader::open(&path) {
+        Ok(reader) => reader,
+        Err(error) => {
+            eprintln!("cannot open {}: {}", path, error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut stats: BTreeMap<String, Stats> = BTreeMap::new();
+    let mut failure = None;
+    for entry in reader {
+        match entry {
+            Ok(entry) => {
+                let name = String::from_utf8_lossy(&entry.command.args()[0]).to_uppercase();
+                let stats = stats.entry(name).or_default();
+                stats.count += 1;
+                stats.bytes += entry.len as u64;
+            }
+            Err(error) => {
+                failure = Some(error);
+                break;
+            }
+        }
+    }
+
+    let mut sorted: Vec<_> = stats.iter().collect();
+    sorted.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.count));
+
+    println!("{:<24} {:>12} {:>14}", "command", "count", "bytes");
+    for (name, stats) in &sorted {
+        println!("{:<24} {:>12} {:>14}", name, stats.count, stats.bytes);
+    }
+    println!(
+        "{:<24} {:>12} {:>14}",
+        "total",
+        stats.values().map(|x| x.count).sum::<u64>(),
+        stats.values().map(|x| x.bytes).sum::<u64>()
+    );
+
+    let Some(error) = failure else {
+        println!("AOF is valid");
+        return ExitCode::SUCCESS;
+    };
+
+    println!("AOF is not valid: {}", error);
+    let Some(valid_len) = error.valid_len() else {
+        return ExitCode::FAILURE;
+    };
+    println!("last valid command ends at offset {}", valid_len);
+
+    if !fix {
+        return ExitCode::FAILURE;
+    }
+
+    let file_len = match std::fs::metadata(&path) {
+        Ok(metadata) => metadata.len(),
+        Err(error) => {
+            eprintln!("cannot read {}: {}", path, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!(
+        "this will discard {} bytes after offset {}",
+        file_len.saturating_sub(valid_len),
+        valid_len
+    );
+    if !matches!(error, AofError::Truncated { .. }) && !force && !confirm() {
+        println!("not truncating {}", path);
+        return ExitCode::FAILURE;
+    }
+
+    let result = std::fs::OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .and_then(|file| file.set_len(valid_len));
+    match result {
+        Ok(()) => {
+            println!("truncated {} to {} bytes", path, valid_len);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("cannot truncate {}: {}", path, error);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,97 @@
+#[cfg(test)]
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::formatter::Formatter;
+use crate::{RespType, RespTypeRef};
+
+/// A request in the form clients send it, an array of bulk strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    args: Vec<Vec<u8>>,
+}
+
+impl Command {
+    pub fn new(name: impl AsRef<[u8]>) -> Command {
+        Command {
+            args: alloc::vec![name.as_ref().to_vec()],
+        }
+    }
+
+    pub fn arg(mut self, arg: impl AsRef<[u8]>) -> Command {
+        self.args.push(arg.as_ref().to_vec());
+        self
+    }
+
+    /// The command name followed by its arguments.
+    pub fn args(&self) -> &[Vec<u8>] {
+        &self.args
+    }
+
+    pub fn as_resp_type_ref(&self) -> RespTypeRef<'_> {
+        RespTypeRef::Array(
+            self.args
+                .iter()
+                .map(|arg| RespTypeRef::BulkString(arg))
+                .collect(),
+        )
+    }
+
+    pub fn write_to_vec(&self, output: &mut Vec<u8>) {
+        // bulk strings can't be rejected
+        Formatter::new_with_defaults(self.as_resp_type_ref())
+            .write_to_vec(output)
+            .expect("a command is always encodable");
+    }
+}
+
+/// Accepts a non-empty array of bulk strings, anything else is handed back.
+impl TryFrom<RespType> for Command {
+    type Error = RespType;
+
+    fn try_from(frame: RespType) -> Result<Self, Self::Error> {
+        match frame {
+            RespType::Array(items)
+                if !items.is_empty()
+                    && items.iter().all(|x| matches!(x, RespType::BulkString(_))) =>
+            {
+                let args = items.into_iter().filter_map(|x| x.into_bytes()).collect();
+                Ok(Command { args })
+            }
+            frame => Err(frame),
+        }
+    }
+}
+
+#[test]
+fn command_encoding() {
+    let command = Command::new("SET")
+        .arg("key")
+        .arg(b"\r\n")
+        .arg(10.to_string());
+
+    let mut output = Vec::new();
+    command.write_to_vec(&mut output);
+
+    assert_eq!(
+        output,
+        b"*4\r\n$3\r\nSET\r\n$3\r\nkey\r\n$2\r\n\r\n\r\n$2\r\n10\r\n"
+    );
+    assert_eq!(
+        crate::Parser::new_from_bytes(&output).parse().unwrap(),
+        command.as_resp_type_ref()
+    );
+}
+
+#[test]
+fn command_from_frame() {
+    let frame = crate::bytes_to_resp_type(b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n").unwrap();
+    assert_eq!(Command::try_from(frame), Ok(Command::new("GET").arg("key")));
+
+    for frame in [
+        RespType::Array(vec![]),
+        RespType::Array(vec![RespType::Integer(1)]),
+    ] {
+        assert_eq!(Command::try_from(frame.clone()), Err(frame));
+    }
+}
@@ -0,0 +1,350 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::Path;
+use std::time::Duration;
+
+use crate::decoder::DecodeError;
+use crate::{Command, Decoder, ParserOptions, RespType};
+
+pub enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    pub fn read_timeout(&self) -> std::io::Result<Option<Duration>> {
+        match self {
+            Stream::Tcp(stream) => stream.read_timeout(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read_timeout(),
+        }
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_write_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_write_timeout(timeout),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Blocking client connection, replies are read with a `Decoder` in the order the commands
+/// were sent.
+pub struct Connection {
+    decoder: Decoder<Stream>,
+    output: Vec<u8>,
+    broken: bool,
+}
+
+impl Connection {
+    pub fn new(stream: Stream) -> Connection {
+        Connection {
+            decoder: Decoder::new(stream),
+            output: Vec::new(),
+            broken: false,
+        }
+    }
+
+    pub fn connect_tcp(address: impl ToSocketAddrs) -> std::io::Result<Connection> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        Ok(Connection::new(Stream::Tcp(stream)))
+    }
+
+    #[cfg(unix)]
+    pub fn connect_unix(path: impl AsRef<Path>) -> std::io::Result<Connection> {
+        Ok(Connection::new(Stream::Unix(UnixStream::connect(path)?)))
+    }
+
+    /// A reply that does not arrive within `timeout` fails `recv` with `WouldBlock` or
+    /// `TimedOut`, depending on the platform.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.decoder.get_ref().set_read_timeout(timeout)
+    }
+
+    pub fn read_timeout(&self) -> std::io::Result<Option<Duration>> {
+        self.decoder.get_ref().read_timeout()
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.decoder.get_ref().set_write_timeout(timeout)
+    }
+
+    pub fn set_parser_options(&mut self, options: ParserOptions) {
+        self.decoder.set_parser_options(options);
+    }
+
+    pub fn get_ref(&self) -> &Stream {
+        self.decoder.get_ref()
+    }
+
+    pub fn send(&mut self, command: &Command) -> std::io::Result<()> {
+        self.send_many(std::slice::from_ref(command))
+    }
+
+    /// Writes all commands at once, their replies can be read with `recv_many`.
+    pub fn send_many(&mut self, commands: &[Command]) -> std::io::Result<()> {
+        self.output.clear();
+        for command in commands {
+            command.write_to_vec(&mut self.output);
+        }
+
+        let stream = self.decoder.get_mut();
+        let result = stream.write_all(&self.output).and_then(|_| stream.flush());
+        self.broken |= result.is_err();
+        result
+    }
+
+    pub fn recv(&mut self) -> Result<RespType, DecodeError> {
+        let result = match self.decoder.decode() {
+            Ok(Some(item)) => Ok(item),
+            Ok(None) => Err(DecodeError::Io(ErrorKind::UnexpectedEof.into())),
+            Err(error) => Err(error),
+        };
+        self.broken |= result.is_err();
+        result
+    }
+
+    /// Whether a send or recv failed, after that replies can no longer be matched to commands
+    /// (e.g. the rest of a malformed frame is still in the stream) and the connection should be
+    /// closed.
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    pub fn recv_many(&mut self, count: usize) -> Result<Vec<RespType>, DecodeError> {
+        (0..count).map(|_| self.recv()).collect()
+    }
+
+    pub fn request(&mut self, command: &Command) -> Result<RespType, DecodeError> {
+        self.send(command)?;
+        self.recv()
+    }
+
+    /// Hands a reply back so its buffers are reused, see `Decoder::recycle`.
+    pub fn recycle(&mut self, item: RespType) {
+        self.decoder.recycle(item);
+    }
+}
+
+// Answers PING, ECHO, SET, GET, SLEEP <ms> and QUIT like a small Redis server, PUSH <data>
+// sends a RESP3 push frame before its reply and GARBAGE replies with an invalid frame.
+#[cfg(test)]
+pub(crate) fn serve_mock<S: Read + Write>(stream: S) {
+    let mut decoder = Decoder::new(stream);
+    let mut store = std::collections::HashMap::new();
+
+    while let Ok(Some(RespType::Array(args))) = decoder.decode() {
+        let args: Vec<Vec<u8>> = args.into_iter().filter_map(|x| x.into_bytes()).collect();
+        let reply: &[u8] = match args.first().map(|x| x.to_ascii_uppercase()).as_deref() {
+            Some(b"PING") => b"+PONG\r\n",
+            Some(b"ECHO") => {
+                let mut reply = format!("${}\r\n", args[1].len()).into_bytes();
+                reply.extend_from_slice(&args[1]);
+                reply.extend_from_slice(b"\r\n");
+                decoder.get_mut().write_all(&reply).unwrap();
+                continue;
+            }
+            Some(b"SET") => {
+                store.insert(args[1].clone(), args[2].clone());
+                b"+OK\r\n"
+            }
+            Some(b"GET") => {
+                let reply = match store.get(&args[1]) {
+                    Some(value) => {
+                        let mut reply = format!("${}\r\n", value.len()).into_bytes();
+                        reply.extend_from_slice(value);
+                        reply.extend_from_slice(b"\r\n");
+                        reply
+                    }
+                    None => b"$-1\r\n".to_vec(),
+                };
+                decoder.get_mut().write_all(&reply).unwrap();
+                continue;
+            }
+            Some(b"PUSH") => {
+                let mut reply =
+                    format!(">2\r\n$7\r\nmessage\r\n${}\r\n", args[1].len()).into_bytes();
+                reply.extend_from_slice(&args[1]);
+                reply.extend_from_slice(b"\r\n+OK\r\n");
+                decoder.get_mut().write_all(&reply).unwrap();
+                continue;
+            }
+            Some(b"SLEEP") => {
+                let millis = std::str::from_utf8(&args[1]).unwrap().parse().unwrap();
+                std::thread::sleep(Duration::from_millis(millis));
+                b"+OK\r\n"
+            }
+            Some(b"GARBAGE") => b"&oops\r\n",
+            Some(b"QUIT") => {
+                decoder.get_mut().write_all(b"+OK\r\n").unwrap();
+                return;
+            }
+            _ => b"-ERR unknown command\r\n",
+        };
+
+        if decoder.get_mut().write_all(reply).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn spawn_mock_server() -> std::net::SocketAddr {
+    spawn_counting_mock_server().0
+}
+
+// Also counts the connections that were closed, by the client or with QUIT.
+#[cfg(test)]
+pub(crate) fn spawn_counting_mock_server() -> (
+    std::net::SocketAddr,
+    std::sync::Arc<std::sync::atomic::AtomicUsize>,
+) {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let closed = Arc::new(AtomicUsize::new(0));
+    let counter = closed.clone();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let counter = counter.clone();
+            std::thread::spawn(move || {
+                serve_mock(stream);
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+    });
+
+    (address, closed)
+}
+
+#[test]
+fn connection_request() {
+    let mut connection = Connection::connect_tcp(spawn_mock_server()).unwrap();
+
+    assert_eq!(
+        connection.request(&Command::new("PING")).unwrap(),
+        RespType::SimpleString(b"PONG".to_vec())
+    );
+    assert_eq!(
+        connection
+            .request(&Command::new("SET").arg("key").arg("value"))
+            .unwrap(),
+        RespType::SimpleString(b"OK".to_vec())
+    );
+    assert_eq!(
+        connection.request(&Command::new("GET").arg("key")).unwrap(),
+        RespType::BulkString(b"value".to_vec())
+    );
+    assert_eq!(
+        connection.request(&Command::new("NOPE")).unwrap(),
+        RespType::Error(b"ERR unknown command".to_vec())
+    );
+}
+
+#[test]
+fn connection_pipelining() {
+    let mut connection = Connection::connect_tcp(spawn_mock_server()).unwrap();
+
+    let commands: Vec<_> = (0..100)
+        .map(|i| Command::new("ECHO").arg(i.to_string()))
+        .collect();
+    connection.send_many(&commands).unwrap();
+
+    let replies = connection.recv_many(commands.len()).unwrap();
+    for (i, reply) in replies.into_iter().enumerate() {
+        assert_eq!(reply, RespType::BulkString(i.to_string().into_bytes()));
+    }
+}
+
+#[test]
+fn connection_read_timeout() {
+    let mut connection = Connection::connect_tcp(spawn_mock_server()).unwrap();
+    connection
+        .set_read_timeout(Some(Duration::from_millis(20)))
+        .unwrap();
+
+    match connection.request(&Command::new("SLEEP").arg("200")) {
+        Err(DecodeError::Io(error)) => assert!(matches!(
+            error.kind(),
+            ErrorKind::WouldBlock | ErrorKind::TimedOut
+        )),
+        _ => panic!("expected a timeout"),
+    }
+}
+
+#[test]
+fn connection_closed() {
+    let mut connection = Connection::connect_tcp(spawn_mock_server()).unwrap();
+    connection.request(&Command::new("QUIT")).unwrap();
+
+    match connection.request(&Command::new("PING")) {
+        Err(DecodeError::Io(_)) => (),
+        _ => panic!("expected the connection to be closed"),
+    }
+    assert!(connection.is_broken());
+}
+
+#[cfg(unix)]
+#[test]
+fn connection_unix_socket() {
+    let path = std::env::temp_dir().join(format!("redis_resp_{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    std::thread::spawn(move || serve_mock(listener.accept().unwrap().0));
+
+    let mut connection = Connection::connect_unix(&path).unwrap();
+    assert_eq!(
+        connection
+            .request(&Command::new("ECHO").arg("unix"))
+            .unwrap(),
+        RespType::BulkString(b"unix".to_vec())
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,594 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "tokio")]
+use std::future::Future;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+
+use crate::decoder::DecodeError;
+#[cfg(feature = "tokio")]
+use crate::AsyncConnection;
+use crate::{Command, Connection, RespType};
+
+/// Limits for `ConnectionPool` and `AsyncConnectionPool`. Connections that were idle for
+/// longer than `max_idle` or were opened longer than `max_lifetime` ago are closed instead of
+/// handed out again. With `health_check` an idle connection has to answer `PING` within
+/// `health_check_timeout` first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolOptions {
+    pub max_size: usize,
+    pub max_idle: Option<Duration>,
+    pub max_lifetime: Option<Duration>,
+    pub health_check: bool,
+    pub health_check_timeout: Duration,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        PoolOptions {
+            max_size: 16,
+            max_idle: None,
+            max_lifetime: None,
+            health_check: true,
+            health_check_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+impl PoolOptions {
+    fn is_expired(&self, created: Instant) -> bool {
+        self.max_lifetime
+            .is_some_and(|max_lifetime| created.elapsed() > max_lifetime)
+    }
+
+    fn is_stale<C>(&self, idle: &Idle<C>) -> bool {
+        self.is_expired(idle.created)
+            || self
+                .max_idle
+                .is_some_and(|max_idle| idle.returned.elapsed() > max_idle)
+    }
+}
+
+struct Idle<C> {
+    connection: C,
+    created: Instant,
+    returned: Instant,
+}
+
+fn is_pong(reply: Result<RespType, DecodeError>) -> bool {
+    matches!(reply, Ok(RespType::SimpleString(pong)) if pong == b"PONG")
+}
+
+// The read timeout set by `connect` is restored afterwards. A connection that timed out is
+// broken, the late reply would be taken for the next one.
+fn is_healthy(connection: &mut Connection, timeout: Duration) -> bool {
+    let Ok(previous) = connection.read_timeout() else {
+        return false;
+    };
+
+    connection.set_read_timeout(Some(timeout)).is_ok()
+        && is_pong(connection.request(&Command::new("PING")))
+        && connection.set_read_timeout(previous).is_ok()
+}
+
+struct State {
+    idle: Vec<Idle<Connection>>,
+    open: usize,
+}
+
+/// Bounded pool of blocking connections, `get` waits while `max_size` connections are in use.
+/// A connection that `is_broken` when it is returned is closed.
+pub struct ConnectionPool {
+    connect: Box<dyn Fn() -> std::io::Result<Connection> + Send + Sync>,
+    options: PoolOptions,
+    state: Mutex<State>,
+    released: Condvar,
+}
+
+impl ConnectionPool {
+    pub fn new(
+        options: PoolOptions,
+        connect: impl Fn() -> std::io::Result<Connection> + Send + Sync + 'static,
+    ) -> ConnectionPool {
+        ConnectionPool {
+            connect: Box::new(connect),
+            options,
+            state: Mutex::new(State {
+                idle: Vec::new(),
+                open: 0,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn get(&self) -> Result<PooledConnection<'_>, DecodeError> {
+        loop {
+            match self.checkout() {
+                Some(mut idle) => {
+                    if !self.options.health_check
+                        || is_healthy(&mut idle.connection, self.options.health_check_timeout)
+                    {
+                        return Ok(PooledConnection {
+                            pool: self,
+                            connection: Some(idle.connection),
+                            created: idle.created,
+                        });
+                    }
+                    self.discard();
+                }
+                None => {
+                    let connection = (self.connect)().inspect_err(|_| self.discard())?;
+                    return Ok(PooledConnection {
+                        pool: self,
+                        connection: Some(connection),
+                        created: Instant::now(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Connections that are currently open, idle or in use.
+    pub fn open_connections(&self) -> usize {
+        self.state.lock().unwrap().open
+    }
+
+    pub fn idle_connections(&self) -> usize {
+        self.state.lock().unwrap().idle.len()
+    }
+
+    // Returns an idle connection, or `None` after reserving room for a new one.
+    fn checkout(&self) -> Option<Idle<Connection>> {
+        let mut state = self.state.lock().unwrap();
+
+        let before = state.idle.len();
+        state.idle.retain(|idle| !self.options.is_stale(idle));
+        state.open -= before - state.idle.len();
+
+        loop {
+            if let Some(idle) = state.idle.pop() {
+                return Some(idle);
+            }
+
+            if state.open < self.options.max_size {
+                state.open += 1;
+                return None;
+            }
+
+            state = self.released.wait(state).unwrap();
+        }
+    }
+
+    fn discard(&self) {
+        self.state.lock().unwrap().open -= 1;
+        self.released.notify_all();
+    }
+
+    fn release(&self, connection: Connection, created: Instant) {
+        if connection.is_broken() || self.options.is_expired(created) {
+            self.discard();
+            return;
+        }
+
+        self.state.lock().unwrap().idle.push(Idle {
+            connection,
+            created,
+            returned: Instant::now(),
+        });
+        self.released.notify_one();
+    }
+}
+
+/// A connection borrowed from a `ConnectionPool`, it goes back to the pool when dropped.
+pub struct PooledConnection<'a> {
+    pool: &'a ConnectionPool,
+    connection: Option<Connection>,
+    created: Instant,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.connection.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.connection.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.release(connection, self.created);
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+type ConnectFuture = Pin<Box<dyn Future<Output = std::io::Result<AsyncConnection>> + Send>>;
+
+/// Async variant of `ConnectionPool`. An `AsyncConnection` closes itself after a protocol
+/// error, closed connections are not returned to the pool. Connections the pool discards are
+/// closed, also when `connect` kept a clone.
+#[cfg(feature = "tokio")]
+pub struct AsyncConnectionPool {
+    connect: Box<dyn Fn() -> ConnectFuture + Send + Sync>,
+    options: PoolOptions,
+    idle: Mutex<Vec<Idle<AsyncConnection>>>,
+    permits: tokio::sync::Semaphore,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncConnectionPool {
+    /// Push frames of the connections made by `connect` are dropped.
+    pub fn new<F, Fut>(options: PoolOptions, connect: F) -> AsyncConnectionPool
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = std::io::Result<AsyncConnection>> + Send + 'static,
+    {
+        AsyncConnectionPool {
+            connect: Box::new(move || Box::pin(connect())),
+            options,
+            idle: Mutex::new(Vec::new()),
+            permits: tokio::sync::Semaphore::new(options.max_size),
+        }
+    }
+
+    pub async fn get(&self) -> Result<AsyncPooledConnection<'_>, DecodeError> {
+        // the semaphore is never closed
+        let permit = self.permits.acquire().await.unwrap();
+
+        loop {
+            let idle = {
+                let mut idle = self.idle.lock().unwrap();
+                idle.retain(|idle| {
+                    let keep = !idle.connection.is_closed() && !self.options.is_stale(idle);
+                    if !keep {
+                        idle.connection.close();
+                    }
+                    keep
+                });
+                idle.pop()
+            };
+
+            let (connection, created) = match idle {
+                Some(idle) => {
+                    if self.options.health_check {
+                        let ping = Command::new("PING");
+                        let reply = tokio::time::timeout(
+                            self.options.health_check_timeout,
+                            idle.connection.request(&ping),
+                        )
+                        .await;
+                        if !reply.is_ok_and(is_pong) {
+                            idle.connection.close();
+                            continue;
+                        }
+                    }
+                    (idle.connection, idle.created)
+                }
+                None => ((self.connect)().await?, Instant::now()),
+            };
+
+            return Ok(AsyncPooledConnection {
+                pool: self,
+                connection: Some(connection),
+                created,
+                _permit: permit,
+            });
+        }
+    }
+
+    pub fn idle_connections(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    fn release(&self, connection: AsyncConnection, created: Instant) {
+        if connection.is_closed() || self.options.is_expired(created) {
+            connection.close();
+            return;
+        }
+
+        self.idle.lock().unwrap().push(Idle {
+            connection,
+            created,
+            returned: Instant::now(),
+        });
+    }
+}
+
+/// A connection borrowed from an `AsyncConnectionPool`, it goes back to the pool when dropped.
+/// It does not deref to `AsyncConnection`, a clone could still be used after the connection
+/// was handed to someone else.
+#[cfg(feature = "tokio")]
+pub struct AsyncPooledConnection<'a> {
+    pool: &'a AsyncConnectionPool,
+    connection: Option<AsyncConnection>,
+    created: Instant,
+    _permit: tokio::sync::SemaphorePermit<'a>,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncPooledConnection<'_> {
+    pub async fn request(&self, command: &Command) -> Result<RespType, DecodeError> {
+        self.connection().request(command).await
+    }
+
+    pub async fn pipeline(&self, commands: &[Command]) -> Result<Vec<RespType>, DecodeError> {
+        self.connection().pipeline(commands).await
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.connection().is_closed()
+    }
+
+    fn connection(&self) -> &AsyncConnection {
+        self.connection.as_ref().unwrap()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for AsyncPooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.release(connection, self.created);
+        }
+    }
+}
+
+#[cfg(test)]
+fn counting_pool(options: PoolOptions) -> (ConnectionPool, std::sync::Arc<Mutex<usize>>) {
+    let address = crate::connection::spawn_mock_server();
+    let connects = std::sync::Arc::new(Mutex::new(0));
+    let counter = connects.clone();
+
+    let pool = ConnectionPool::new(options, move || {
+        *counter.lock().unwrap() += 1;
+        Connection::connect_tcp(address)
+    });
+
+    (pool, connects)
+}
+
+#[test]
+fn connection_pool_reuses_connections() {
+    let (pool, connects) = counting_pool(PoolOptions::default());
+
+    for _ in 0..3 {
+        let mut connection = pool.get().unwrap();
+        assert_eq!(
+            connection.request(&Command::new("PING")).unwrap(),
+            RespType::SimpleString(b"PONG".to_vec())
+        );
+    }
+
+    assert_eq!(*connects.lock().unwrap(), 1);
+    assert_eq!(pool.idle_connections(), 1);
+}
+
+#[test]
+fn connection_pool_is_bounded() {
+    let (pool, connects) = counting_pool(PoolOptions {
+        max_size: 2,
+        ..PoolOptions::default()
+    });
+
+    std::thread::scope(|scope| {
+        for i in 0..8 {
+            let pool = &pool;
+            scope.spawn(move || {
+                let mut connection = pool.get().unwrap();
+                assert!(pool.open_connections() <= 2);
+                assert_eq!(
+                    connection
+                        .request(&Command::new("ECHO").arg(i.to_string()))
+                        .unwrap(),
+                    RespType::BulkString(i.to_string().into_bytes())
+                );
+            });
+        }
+    });
+
+    assert!(*connects.lock().unwrap() <= 2);
+}
+
+#[test]
+fn connection_pool_discards_broken_connections() {
+    let (pool, connects) = counting_pool(PoolOptions::default());
+
+    {
+        let mut connection = pool.get().unwrap();
+        assert!(matches!(
+            connection.request(&Command::new("GARBAGE")),
+            Err(DecodeError::Parse(_))
+        ));
+    }
+    assert_eq!(pool.open_connections(), 0);
+
+    // the server closed this one, the health check notices before it is handed out
+    pool.get().unwrap().request(&Command::new("QUIT")).unwrap();
+    assert_eq!(pool.idle_connections(), 1);
+
+    let mut connection = pool.get().unwrap();
+    assert!(connection.request(&Command::new("PING")).is_ok());
+    assert_eq!(*connects.lock().unwrap(), 3);
+}
+
+#[test]
+fn connection_pool_max_idle_and_lifetime() {
+    let (pool, connects) = counting_pool(PoolOptions {
+        max_idle: Some(Duration::from_millis(20)),
+        ..PoolOptions::default()
+    });
+
+    drop(pool.get().unwrap());
+    std::thread::sleep(Duration::from_millis(40));
+    drop(pool.get().unwrap());
+    assert_eq!(*connects.lock().unwrap(), 2);
+    assert_eq!(pool.open_connections(), 1);
+
+    let (pool, connects) = counting_pool(PoolOptions {
+        max_lifetime: Some(Duration::from_millis(20)),
+        ..PoolOptions::default()
+    });
+
+    let connection = pool.get().unwrap();
+    std::thread::sleep(Duration::from_millis(40));
+    drop(connection);
+    assert_eq!(pool.idle_connections(), 0);
+    drop(pool.get().unwrap());
+    assert_eq!(*connects.lock().unwrap(), 2);
+}
+
+// Accepts connections but never answers, counts the ones the client closed.
+#[cfg(test)]
+fn spawn_silent_server() -> (
+    std::net::SocketAddr,
+    std::sync::Arc<std::sync::atomic::AtomicUsize>,
+) {
+    use std::io::Read;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let closed = std::sync::Arc::new(AtomicUsize::new(0));
+    let counter = closed.clone();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let counter = counter.clone();
+            std::thread::spawn(move || {
+                while stream.read(&mut [0; 64]).is_ok_and(|read| read > 0) {}
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+    });
+
+    (address, closed)
+}
+
+#[test]
+fn connection_pool_health_check_timeout() {
+    let (address, _) = spawn_silent_server();
+
+    let pool = ConnectionPool::new(
+        PoolOptions {
+            health_check_timeout: Duration::from_millis(50),
+            ..PoolOptions::default()
+        },
+        move || {
+            let connection = Connection::connect_tcp(address)?;
+            connection.set_read_timeout(Some(Duration::from_secs(30)))?;
+            Ok(connection)
+        },
+    );
+
+    drop(pool.get().unwrap());
+    let start = Instant::now();
+    let connection = pool.get().unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(pool.open_connections(), 1);
+    assert_eq!(
+        connection.read_timeout().unwrap(),
+        Some(Duration::from_secs(30))
+    );
+    drop(connection);
+
+    // a healthy connection keeps the read timeout of `connect`
+    let (pool, _) = counting_pool(PoolOptions::default());
+    drop(pool.get().unwrap());
+    assert_eq!(pool.get().unwrap().read_timeout().unwrap(), None);
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_connection_pool() {
+    let address = crate::connection::spawn_mock_server();
+    let pool = AsyncConnectionPool::new(
+        PoolOptions {
+            max_size: 2,
+            ..PoolOptions::default()
+        },
+        move || async move { Ok(AsyncConnection::connect_tcp(address).await?.0) },
+    );
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        {
+            let first = pool.get().await.unwrap();
+            let second = pool.get().await.unwrap();
+            assert_eq!(
+                first.request(&Command::new("ECHO").arg("a")).await.unwrap(),
+                RespType::BulkString(b"a".to_vec())
+            );
+            assert!(second.request(&Command::new("GARBAGE")).await.is_err());
+        }
+
+        // the desynchronized connection was closed and not returned
+        assert_eq!(pool.idle_connections(), 1);
+        let connection = pool.get().await.unwrap();
+        assert!(connection.request(&Command::new("PING")).await.is_ok());
+    });
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_connection_pool_closes_discarded_connections() {
+    use crate::async_connection::wait_for;
+
+    let (address, closed) = spawn_silent_server();
+    let connect = move || async move { Ok(AsyncConnection::connect_tcp(address).await?.0) };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let pool = AsyncConnectionPool::new(
+            PoolOptions {
+                health_check_timeout: Duration::from_millis(50),
+                ..PoolOptions::default()
+            },
+            connect,
+        );
+
+        drop(pool.get().await.unwrap());
+        // the health check times out
+        drop(pool.get().await.unwrap());
+        wait_for(&closed, 1).await;
+
+        let pool = AsyncConnectionPool::new(
+            PoolOptions {
+                max_idle: Some(Duration::from_millis(20)),
+                max_lifetime: Some(Duration::from_millis(100)),
+                health_check: false,
+                ..PoolOptions::default()
+            },
+            connect,
+        );
+
+        // idle for too long
+        drop(pool.get().await.unwrap());
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        let connection = pool.get().await.unwrap();
+        wait_for(&closed, 2).await;
+
+        // expired while in use
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        drop(connection);
+        wait_for(&closed, 3).await;
+        assert_eq!(pool.idle_connections(), 0);
+    });
+}
@@ -0,0 +1,636 @@
+use std::fmt::Display;
+use std::io::{ErrorKind, Read, Write};
+
+use crate::lexer::{find_newline, parse_size};
+use crate::pool::BufferPool;
+use crate::scanner::FrameScanner;
+use crate::tape::Node;
+use crate::{Lexer, ParseError, Parser, ParserOptions, RespErrorType, RespType};
+
+const READ_SIZE: usize = 8 * 1024;
+// reads grow up to this size for frames that are larger than a single read
+const MAX_READ_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Io(std::io::Error),
+    Parse(RespErrorType),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            DecodeError::Io(error) => write!(f, "{}", error),
+            DecodeError::Parse(error_type) => write!(f, "{:?}", error_type),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<std::io::Error> for DecodeError {
+    fn from(error: std::io::Error) -> Self {
+        DecodeError::Io(error)
+    }
+}
+
+/// Read buffer that scans the frame at its start incrementally, so a frame that arrives in
+/// many reads is not scanned again from the start after every read.
+#[derive(Debug)]
+pub(crate) struct FrameBuffer {
+    // initialized up to its length, `end` marks the data that was read
+    buffer: Vec<u8>,
+    start: usize,
+    end: usize,
+    read_size: usize,
+    scanner: FrameScanner,
+}
+
+impl FrameBuffer {
+    /// `read_size` is the smallest read, files can use larger ones than sockets.
+    pub(crate) fn new(options: ParserOptions, read_size: usize) -> FrameBuffer {
+        FrameBuffer {
+            buffer: Vec::new(),
+            start: 0,
+            end: 0,
+            read_size,
+            scanner: FrameScanner::new(options),
+        }
+    }
+
+    pub(crate) fn options(&self) -> ParserOptions {
+        self.scanner.options()
+    }
+
+    pub(crate) fn set_options(&mut self, options: ParserOptions) {
+        self.scanner = FrameScanner::new(options);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.start = 0;
+        self.end = 0;
+        self.scanner.reset();
+    }
+
+    /// Data that was read but not consumed yet.
+    pub(crate) fn pending(&self) -> &[u8] {
+        &self.buffer[self.start..self.end]
+    }
+
+    pub(crate) fn consume(&mut self, len: usize) {
+        self.start += len;
+        self.scanner.reset();
+    }
+
+    /// Length of the frame at the start of `pending`, `None` until it was read completely.
+    pub(crate) fn frame_len(&mut self) -> Result<Option<usize>, ParseError<'_>> {
+        let pending = &self.buffer[self.start..self.end];
+        if pending.is_empty() || pending.len() < self.scanner.wanted() {
+            return Ok(None);
+        }
+
+        self.scanner.scan(pending)
+    }
+
+    /// Builds the frame at the start of the pending data once it was read completely.
+    /// `nodes` is the tape reused for every frame, the values come from `pool`.
+    pub(crate) fn decode(
+        &mut self,
+        nodes: &mut Vec<Node>,
+        pool: &mut BufferPool,
+    ) -> Result<Option<RespType>, DecodeError> {
+        let Some(length) = self
+            .frame_len()
+            .map_err(|error| DecodeError::Parse(error.error_type()))?
+        else {
+            return Ok(None);
+        };
+
+        let frame = &self.buffer[self.start..self.start + length];
+        let tape = Parser::new_with_options(Lexer::new(frame), self.options())
+            .parse_tape_with(std::mem::take(nodes))
+            .map_err(|error| DecodeError::Parse(error.error_type()))?;
+        let item = pool.build(tape.root());
+        *nodes = tape.into_nodes();
+        self.consume(length);
+
+        Ok(Some(item))
+    }
+
+    /// Free space for the next read, at least what the pending frame is known to miss. Reads
+    /// grow with the pending data, so a long frame is scanned a logarithmic number of times.
+    pub(crate) fn spare(&mut self) -> &mut [u8] {
+        if self.start == self.end {
+            self.start = 0;
+            self.end = 0;
+        } else if self.start > self.end / 2 {
+            self.buffer.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+        }
+
+        let pending = self.end - self.start;
+        let missing = self.scanner.wanted().saturating_sub(pending);
+        let size = missing
+            .max(pending)
+            .clamp(self.read_size, MAX_READ_SIZE.max(self.read_size));
+        if self.buffer.len() < self.end + size {
+            self.buffer.resize(self.end + size, 0);
+        }
+
+        &mut self.buffer[self.end..]
+    }
+
+    /// Marks `len` bytes of `spare` as read.
+    pub(crate) fn filled(&mut self, len: usize) {
+        self.end += len;
+    }
+
+    pub(crate) fn read_from<R: Read>(&mut self, reader: &mut R) -> std::io::Result<usize> {
+        let spare = self.spare();
+        let read = loop {
+            match reader.read(spare) {
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                result => break result?,
+            }
+        };
+
+        self.filled(read);
+        Ok(read)
+    }
+}
+
+/// Incremental decoder that reads frames from a socket, file or any other `Read` source.
+pub struct Decoder<R> {
+    reader: R,
+    buffer: FrameBuffer,
+    large_bulk_threshold: Option<usize>,
+    unread_bulk: Option<usize>,
+    // reused for every frame, see `recycle`
+    nodes: Vec<Node>,
+    pool: BufferPool,
+}
+
+pub enum Frame<'d, R> {
+    Value(RespType),
+    BulkString(BulkStringReader<'d, R>),
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(reader: R) -> Decoder<R> {
+        Decoder {
+            reader,
+            buffer: FrameBuffer::new(ParserOptions::default(), READ_SIZE),
+            large_bulk_threshold: None,
+            unread_bulk: None,
+            nodes: Vec::new(),
+            pool: BufferPool::default(),
+        }
+    }
+
+    /// Swaps in a new source and drops all buffered input, the buffers keep their capacity.
+    pub fn reset(&mut self, reader: R) -> R {
+        self.buffer.clear();
+        self.unread_bulk = None;
+        std::mem::replace(&mut self.reader, reader)
+    }
+
+    /// Hands a decoded frame back, its buffers are reused for the next frames.
+    pub fn recycle(&mut self, item: RespType) {
+        self.pool.recycle(item);
+    }
+
+    /// Top level bulk strings larger than `threshold` are returned by `next_frame` as a
+    /// `BulkStringReader` instead of being buffered.
+    pub fn set_large_bulk_threshold(&mut self, threshold: Option<usize>) {
+        self.large_bulk_threshold = threshold;
+    }
+
+    /// Mode and limits used to scan and parse every frame.
+    pub fn set_parser_options(&mut self, options: ParserOptions) {
+        self.buffer.set_options(options);
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Bytes that were read from the source but not decoded yet.
+    pub fn buffered(&self) -> &[u8] {
+        self.buffer.pending()
+    }
+
+    pub fn decode(&mut self) -> Result<Option<RespType>, DecodeError> {
+        self.discard_unread_bulk()?;
+
+        loop {
+            if let Some(item) = self.buffer.decode(&mut self.nodes, &mut self.pool)? {
+                return Ok(Some(item));
+            }
+
+            if self.buffer.read_from(&mut self.reader)? == 0 {
+                if self.buffer.pending().is_empty() {
+                    return Ok(None);
+                }
+
+                return Err(DecodeError::Io(ErrorKind::UnexpectedEof.into()));
+            }
+        }
+    }
+
+    pub fn next_frame(&mut self) -> Result<Option<Frame<'_, R>>, DecodeError> {
+        self.discard_unread_bulk()?;
+
+        if let Some(threshold) = self.large_bulk_threshold {
+            if let Some(size) = self.take_large_bulk_header(threshold)? {
+                return Ok(Some(Frame::BulkString(BulkStringReader {
+                    decoder: self,
+                    size,
+                    remaining: size,
+                    finished: false,
+                })));
+            }
+        }
+
+        Ok(self.decode()?.map(Frame::Value))
+    }
+
+    fn take_large_bulk_header(&mut self, threshold: usize) -> Result<Option<usize>, DecodeError> {
+        loop {
+            let pending = self.buffer.pending();
+
+            match pending.first() {
+                Some(b'$') => {
+                    if let Some(end) = find_newline(pending) {
+                        let size = parse_size(&pending[1..end]).filter(|size| *size > threshold);
+                        if size
+                            .zip(self.buffer.options().max_bulk_len)
+                            .is_some_and(|(size, max)| size > max)
+                        {
+                            return Err(DecodeError::Parse(RespErrorType::LimitExceeded));
+                        }
+                        if size.is_some() {
+                            self.buffer.consume(end + 2);
+                        }
+
+                        return Ok(size);
+                    }
+                }
+                Some(_) => return Ok(None),
+                None => (),
+            }
+
+            if self.buffer.read_from(&mut self.reader)? == 0 {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn read_payload(&mut self, output: &mut [u8]) -> std::io::Result<usize> {
+        let pending = self.buffer.pending();
+        if pending.is_empty() {
+            return loop {
+                match self.reader.read(output) {
+                    Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                    result => break result,
+                }
+            };
+        }
+
+        let length = pending.len().min(output.len());
+        output[..length].copy_from_slice(&pending[..length]);
+        self.buffer.consume(length);
+
+        Ok(length)
+    }
+
+    fn read_bulk_end(&mut self) -> std::io::Result<()> {
+        while self.buffer.pending().len() < 2 {
+            if self.buffer.read_from(&mut self.reader)? == 0 {
+                return Err(ErrorKind::UnexpectedEof.into());
+            }
+        }
+
+        if &self.buffer.pending()[..2] != b"\r\n" {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                DecodeError::Parse(RespErrorType::NewLineMissing),
+            ));
+        }
+        self.buffer.consume(2);
+
+        Ok(())
+    }
+
+    fn discard_unread_bulk(&mut self) -> std::io::Result<()> {
+        if let Some(mut remaining) = self.unread_bulk {
+            let mut scratch = [0; 512];
+
+            while remaining > 0 {
+                let wanted = remaining.min(scratch.len());
+                match self.read_payload(&mut scratch[..wanted])? {
+                    0 => return Err(ErrorKind::UnexpectedEof.into()),
+                    read => remaining -= read,
+                }
+                self.unread_bulk = Some(remaining);
+            }
+
+            self.read_bulk_end()?;
+            self.unread_bulk = None;
+        }
+
+        Ok(())
+    }
+}
+
+/// Bounded reader over the payload of a large bulk string, the trailing newline is
+/// consumed once the payload has been read completely.
+pub struct BulkStringReader<'d, R> {
+    decoder: &'d mut Decoder<R>,
+    size: usize,
+    remaining: usize,
+    finished: bool,
+}
+
+impl<'d, R: Read> BulkStringReader<'d, R> {
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    pub fn copy_to<W: Write>(&mut self, output: &mut W) -> std::io::Result<u64> {
+        std::io::copy(self, output)
+    }
+}
+
+impl<'d, R: Read> Read for BulkStringReader<'d, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 {
+            if !self.finished {
+                self.decoder.read_bulk_end()?;
+                self.finished = true;
+            }
+            return Ok(0);
+        }
+
+        let wanted = buf.len().min(self.remaining);
+        match self.decoder.read_payload(&mut buf[..wanted])? {
+            0 if wanted > 0 => Err(ErrorKind::UnexpectedEof.into()),
+            read => {
+                self.remaining -= read;
+                Ok(read)
+            }
+        }
+    }
+}
+
+impl<'d, R> Drop for BulkStringReader<'d, R> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.decoder.unread_bulk = Some(self.remaining);
+        }
+    }
+}
+
+#[cfg(test)]
+struct OneByteReader<'a>(&'a [u8]);
+
+#[cfg(test)]
+impl<'a> Read for OneByteReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.0.is_empty() || buf.is_empty() {
+            return Ok(0);
+        }
+
+        buf[0] = self.0[0];
+        self.0 = &self.0[1..];
+        Ok(1)
+    }
+}
+
+#[test]
+fn decoder_multiple_frames() {
+    let mut decoder = Decoder::new(&b"+OK\r\n:12\r\n*2\r\n$3\r\nfoo\r\n$-1\r\n"[..]);
+
+    assert_eq!(
+        decoder.decode().unwrap(),
+        Some(RespType::SimpleString(b"OK".to_vec()))
+    );
+    assert_eq!(decoder.decode().unwrap(), Some(RespType::Integer(12)));
+    assert_eq!(
+        decoder.decode().unwrap(),
+        Some(RespType::Array(vec![
+            RespType::BulkString(b"foo".to_vec()),
+            RespType::NullString
+        ]))
+    );
+    assert_eq!(decoder.decode().unwrap(), None);
+}
+
+#[test]
+fn decoder_incremental_input() {
+    let mut decoder = Decoder::new(OneByteReader(b"*2\r\n$5\r\nhe\r\nl\r\n:-3\r\n+OK\r\n"));
+
+    assert_eq!(
+        decoder.decode().unwrap(),
+        Some(RespType::Array(vec![
+            RespType::BulkString(b"he\r\nl".to_vec()),
+            RespType::Integer(-3)
+        ]))
+    );
+    assert_eq!(
+        decoder.decode().unwrap(),
+        Some(RespType::SimpleString(b"OK".to_vec()))
+    );
+    assert_eq!(decoder.decode().unwrap(), None);
+}
+
+#[test]
+fn decoder_large_frame_in_small_reads() {
+    struct SmallReads<'a>(&'a [u8]);
+
+    impl<'a> Read for SmallReads<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let length = self.0.len().min(buf.len()).min(7);
+            buf[..length].copy_from_slice(&self.0[..length]);
+            self.0 = &self.0[length..];
+            Ok(length)
+        }
+    }
+
+    let mut data = b"*20000\r\n".to_vec();
+    for _ in 0..20000 {
+        data.extend_from_slice(b"$3\r\nfoo\r\n");
+    }
+    data.extend_from_slice(b"$100000\r\n");
+    data.resize(data.len() + 100000, b'x');
+    data.extend_from_slice(b"\r\n");
+
+    let mut decoder = Decoder::new(SmallReads(&data));
+    match decoder.decode().unwrap() {
+        Some(RespType::Array(items)) => assert_eq!(items.len(), 20000),
+        _ => panic!("expected an array"),
+    }
+    match decoder.decode().unwrap() {
+        Some(RespType::BulkString(payload)) => assert_eq!(payload.len(), 100000),
+        _ => panic!("expected a bulk string"),
+    }
+    assert_eq!(decoder.decode().unwrap(), None);
+}
+
+#[test]
+fn decoder_truncated_frame() {
+    let mut decoder = Decoder::new(&b"$10\r\nhello"[..]);
+
+    match decoder.decode() {
+        Err(DecodeError::Io(error)) => assert_eq!(error.kind(), ErrorKind::UnexpectedEof),
+        _ => panic!("expected an unexpected eof error"),
+    }
+}
+
+#[test]
+fn decoder_invalid_frame() {
+    let mut decoder = Decoder::new(&b"?huh\r\n"[..]);
+
+    match decoder.decode() {
+        Err(DecodeError::Parse(error_type)) => assert_eq!(error_type, RespErrorType::InvalidData),
+        _ => panic!("expected a parse error"),
+    }
+}
+
+#[test]
+fn decoder_huge_aggregate_size() {
+    let mut decoder = Decoder::new(&b"%4611686018427387904\r\n"[..]);
+    assert!(matches!(
+        decoder.decode(),
+        Err(DecodeError::Parse(RespErrorType::InvalidSize))
+    ));
+}
+
+#[test]
+fn decoder_large_bulk_string_reader() {
+    let mut decoder = Decoder::new(OneByteReader(b"$10\r\n0123\r\n6789\r\n+OK\r\n"));
+    decoder.set_large_bulk_threshold(Some(4));
+
+    let mut output = Vec::new();
+    match decoder.next_frame().unwrap() {
+        Some(Frame::BulkString(mut reader)) => {
+            assert_eq!(reader.size(), 10);
+            assert_eq!(reader.copy_to(&mut output).unwrap(), 10);
+            assert_eq!(reader.remaining(), 0);
+        }
+        _ => panic!("expected a bulk string reader"),
+    }
+    assert_eq!(output, b"0123\r\n6789");
+
+    match decoder.next_frame().unwrap() {
+        Some(Frame::Value(value)) => assert_eq!(value, RespType::SimpleString(b"OK".to_vec())),
+        _ => panic!("expected a value"),
+    }
+    assert!(decoder.next_frame().unwrap().is_none());
+}
+
+#[test]
+fn decoder_large_bulk_string_below_threshold() {
+    let mut decoder = Decoder::new(&b"$4\r\n0123\r\n"[..]);
+    decoder.set_large_bulk_threshold(Some(4));
+
+    match decoder.next_frame().unwrap() {
+        Some(Frame::Value(value)) => assert_eq!(value, RespType::BulkString(b"0123".to_vec())),
+        _ => panic!("expected a value"),
+    };
+}
+
+#[test]
+fn decoder_large_bulk_string_dropped_reader() {
+    let mut decoder = Decoder::new(&b"$10\r\n0123456789\r\n:1\r\n"[..]);
+    decoder.set_large_bulk_threshold(Some(4));
+
+    match decoder.next_frame().unwrap() {
+        Some(Frame::BulkString(mut reader)) => {
+            let mut start = [0; 3];
+            reader.read_exact(&mut start).unwrap();
+            assert_eq!(&start, b"012");
+        }
+        _ => panic!("expected a bulk string reader"),
+    }
+
+    assert_eq!(decoder.decode().unwrap(), Some(RespType::Integer(1)));
+}
+
+#[test]
+fn decoder_large_bulk_string_missing_newline() {
+    let mut decoder = Decoder::new(&b"$5\r\nhelloXX"[..]);
+    decoder.set_large_bulk_threshold(Some(1));
+
+    let mut output = Vec::new();
+    match decoder.next_frame().unwrap() {
+        Some(Frame::BulkString(mut reader)) => {
+            let error = reader.copy_to(&mut output).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+        _ => panic!("expected a bulk string reader"),
+    };
+}
+
+#[test]
+fn decoder_resp3_frames() {
+    let mut decoder = Decoder::new(OneByteReader(
+        b">2\r\n+message\r\n%1\r\n#t\r\n,1.5\r\n$?\r\n;3\r\nabc\r\n;0\r\n*?\r\n_\r\n.\r\n",
+    ));
+
+    assert_eq!(
+        decoder.decode().unwrap(),
+        Some(RespType::Push(vec![
+            RespType::SimpleString(b"message".to_vec()),
+            RespType::Map(vec![(RespType::Boolean(true), RespType::Double(1.5))])
+        ]))
+    );
+    assert_eq!(
+        decoder.decode().unwrap(),
+        Some(RespType::BulkString(b"abc".to_vec()))
+    );
+    assert_eq!(
+        decoder.decode().unwrap(),
+        Some(RespType::Array(vec![RespType::Null]))
+    );
+    assert_eq!(decoder.decode().unwrap(), None);
+}
+
+#[test]
+fn decoder_limits() {
+    let mut decoder = Decoder::new(&b"*1\r\n*1\r\n:1\r\n$10\r\n0123456789\r\n"[..]);
+    decoder.set_parser_options(ParserOptions {
+        max_depth: Some(1),
+        max_bulk_len: Some(8),
+        ..Default::default()
+    });
+
+    assert!(matches!(
+        decoder.decode(),
+        Err(DecodeError::Parse(RespErrorType::LimitExceeded))
+    ));
+
+    let mut decoder = Decoder::new(&b"$10\r\n0123456789\r\n"[..]);
+    decoder.set_large_bulk_threshold(Some(4));
+    decoder.set_parser_options(ParserOptions {
+        max_bulk_len: Some(8),
+        ..Default::default()
+    });
+
+    assert!(matches!(
+        decoder.next_frame(),
+        Err(DecodeError::Parse(RespErrorType::LimitExceeded))
+    ));
+}
@@ -0,0 +1,183 @@
+use crate::rdb::{crc64, write_value, Input, RdbError, RdbValue};
+use crate::{Command, RespType};
+
+/// The value `DUMP` returns and `RESTORE` takes: a single object in RDB encoding followed by
+/// the RDB version and a CRC64 of everything before the checksum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DumpPayload {
+    /// `RESTORE` refuses payloads with a newer RDB version than the server writes itself.
+    pub version: u16,
+    pub value: RdbValue,
+}
+
+impl DumpPayload {
+    /// Version 9 is understood since Redis 5.0, `to_bytes` raises it when the value needs a
+    /// newer encoding.
+    pub fn new(value: RdbValue) -> DumpPayload {
+        DumpPayload { version: 9, value }
+    }
+
+    pub fn from_bytes(payload: &[u8]) -> Result<DumpPayload, RdbError> {
+        let (body, footer) = payload
+            .split_at_checked(payload.len().wrapping_sub(10))
+            .ok_or(RdbError::Invalid("dump payload"))?;
+
+        let expected = u64::from_le_bytes(footer[2..].try_into().unwrap());
+        let actual = crc64(0, &payload[..payload.len() - 8]);
+        if expected != actual {
+            return Err(RdbError::ChecksumMismatch { expected, actual });
+        }
+
+        let mut input = Input::new(body);
+        let value_type = input.byte()?;
+        let value = input.object(value_type)?;
+        if !input.into_inner().is_empty() {
+            return Err(RdbError::Invalid("dump payload"));
+        }
+
+        Ok(DumpPayload {
+            version: u16::from_le_bytes([footer[0], footer[1]]),
+            value,
+        })
+    }
+
+    /// Fails for empty collections and module values, which Redis cannot restore.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, RdbError> {
+        let mut output = Vec::new();
+        let version = write_value(&mut output, &self.value)?.max(self.version);
+        output.extend_from_slice(&version.to_le_bytes());
+        let crc = crc64(0, &output);
+        output.extend_from_slice(&crc.to_le_bytes());
+        Ok(output)
+    }
+
+    /// `RESTORE key ttl payload`, a ttl of 0 keeps the key forever. Options like `REPLACE`
+    /// can be added with `arg`.
+    pub fn restore_command(&self, key: impl AsRef<[u8]>, ttl: u64) -> Result<Command, RdbError> {
+        Ok(Command::new("RESTORE")
+            .arg(key)
+            .arg(ttl.to_string())
+            .arg(self.to_bytes()?))
+    }
+}
+
+impl TryFrom<&RespType> for DumpPayload {
+    type Error = RdbError;
+
+    fn try_from(reply: &RespType) -> Result<Self, Self::Error> {
+        match reply {
+            RespType::BulkString(payload) => DumpPayload::from_bytes(payload),
+            _ => Err(RdbError::Invalid("DUMP reply")),
+        }
+    }
+}
+
+#[test]
+fn dump_decode_redis_payload() {
+    // DUMP of the integer 10, from the Redis documentation
+    let reply =
+        crate::bytes_to_resp_type(b"$13\r\n\x00\xc0\n\t\x00\xbem\x06\x89Z(\x00\n\r\n").unwrap();
+    let payload = DumpPayload::try_from(&reply).unwrap();
+    assert_eq!(payload.version, 9);
+    assert_eq!(payload.value, RdbValue::String(b"10".to_vec()));
+
+    assert_eq!(
+        DumpPayload::from_bytes(&payload.to_bytes().unwrap()).unwrap(),
+        payload
+    );
+}
+
+#[test]
+fn dump_round_trip() {
+    use crate::rdb::{Stream, StreamConsumer, StreamEntry, StreamGroup, StreamId, StreamPending};
+
+    let id = |ms, seq| StreamId { ms, seq };
+    let stream = Stream {
+        entries: vec![
+            StreamEntry {
+                id: id(1000, 5),
+                fields: vec![(b"a".to_vec(), b"1".to_vec())],
+            },
+            // lower sequence than the master entry and different fields
+            StreamEntry {
+                id: id(1001, 0),
+                fields: vec![(b"b".to_vec(), vec![b'x'; 300])],
+            },
+        ],
+        length: 2,
+        last_id: id(1001, 0),
+        first_id: Some(id(1000, 5)),
+        max_deleted_id: Some(id(0, 0)),
+        entries_added: Some(2),
+        groups: vec![StreamGroup {
+            name: b"group".to_vec(),
+            last_id: id(1000, 5),
+            entries_read: None,
+            pending: vec![StreamPending {
+                id: id(1000, 5),
+                delivery_time: 1_700_000_000_000,
+                delivery_count: 1,
+            }],
+            consumers: vec![StreamConsumer {
+                name: b"consumer".to_vec(),
+                seen_time: 1_700_000_000_000,
+                active_time: Some(1_700_000_000_000),
+                pending: vec![id(1000, 5)],
+            }],
+        }],
+    };
+
+    let values = [
+        (RdbValue::String(b"hello".to_vec()), 9),
+        (RdbValue::List(vec![b"a".to_vec(), b"b".to_vec()]), 9),
+        (RdbValue::Set(vec![b"member".to_vec()]), 9),
+        (
+            RdbValue::SortedSet(vec![
+                (b"low".to_vec(), -1.5),
+                (b"high".to_vec(), f64::INFINITY),
+            ]),
+            9,
+        ),
+        (
+            RdbValue::Hash(vec![(b"field".to_vec(), b"value".to_vec())]),
+            9,
+        ),
+        (RdbValue::Stream(Box::new(stream)), 11),
+    ];
+
+    for (value, version) in values {
+        let payload = DumpPayload::new(value);
+        let decoded = DumpPayload::from_bytes(&payload.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.version, version);
+        assert_eq!(decoded.value, payload.value);
+    }
+
+    let command = DumpPayload::new(RdbValue::String(b"v".to_vec()))
+        .restore_command("key", 0)
+        .unwrap();
+    assert_eq!(
+        command.args()[..3],
+        [b"RESTORE".to_vec(), b"key".to_vec(), b"0".to_vec()]
+    );
+}
+
+#[test]
+fn dump_errors() {
+    let mut payload = DumpPayload::new(RdbValue::String(b"v".to_vec()))
+        .to_bytes()
+        .unwrap();
+    payload[2] ^= 1;
+    assert!(matches!(
+        DumpPayload::from_bytes(&payload),
+        Err(RdbError::ChecksumMismatch { .. })
+    ));
+    assert!(matches!(
+        DumpPayload::from_bytes(b"short"),
+        Err(RdbError::Invalid(_))
+    ));
+    assert!(DumpPayload::try_from(&RespType::Null).is_err());
+    assert!(matches!(
+        DumpPayload::new(RdbValue::List(Vec::new())).to_bytes(),
+        Err(RdbError::Invalid(_))
+    ));
+}
@@ -1,24 +1,94 @@
-use std::io::Write;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Display;
+#[cfg(feature = "std")]
+use std::io::{ErrorKind, Read, Write};
 
 use crate::RespTypeRef;
 
+/// Simple strings and errors can't contain `\r` or `\n`, by default writing them fails with
+/// `EncodeError::UnsafeSimpleString` or `EncodeError::UnsafeError`. With `fallback_to_bulk`
+/// they are written as a bulk string or a RESP3 bulk error instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FormatterOptions {
+    pub fallback_to_bulk: bool,
+}
+
 pub struct Formatter<'a> {
     item: RespTypeRef<'a>,
+    options: FormatterOptions,
 }
 
 impl<'a> Formatter<'a> {
     pub fn new_with_defaults(item: RespTypeRef<'a>) -> Formatter<'a> {
-        Formatter { item }
+        Formatter::new(item, FormatterOptions::default())
+    }
+
+    pub fn new(item: RespTypeRef<'a>, options: FormatterOptions) -> Formatter<'a> {
+        Formatter { item, options }
     }
 
+    /// Items that can't be written fail with `ErrorKind::InvalidInput`, use `try_write` to
+    /// get the `EncodeError`.
+    #[cfg(feature = "std")]
     pub fn write<W: Write>(&self, output: &mut W) -> std::io::Result<()> {
+        self.try_write(output).map_err(|error| match error {
+            EncodeError::Io(error) => error,
+            error => std::io::Error::new(ErrorKind::InvalidInput, error),
+        })
+    }
+
+    /// Nothing is written when the item is rejected.
+    #[cfg(feature = "std")]
+    pub fn try_write<W: Write>(&self, output: &mut W) -> Result<(), EncodeError> {
+        self.validate(&self.item)?;
+        self.inner_write(&mut IoOutput(output), &self.item)
+    }
+
+    /// Appends the encoded item to `output`, this works without the `std` feature. Nothing is
+    /// appended when the item is rejected.
+    pub fn write_to_vec(&self, output: &mut Vec<u8>) -> Result<(), EncodeError> {
+        self.validate(&self.item)?;
         self.inner_write(output, &self.item)
     }
 
-    fn inner_write<W: Write>(&self, output: &mut W, item: &RespTypeRef<'a>) -> std::io::Result<()> {
+    // checks the whole item up front, so a rejected item does not leave half a frame behind
+    fn validate(&self, item: &RespTypeRef<'a>) -> Result<(), EncodeError> {
         use RespTypeRef::*;
 
+        if self.options.fallback_to_bulk {
+            return Ok(());
+        }
+
         match item {
+            SimpleString(data) if !is_line_safe(data) => Err(EncodeError::UnsafeSimpleString),
+            Error(data) if !is_line_safe(data) => Err(EncodeError::UnsafeError),
+            Array(data) | Set(data) | Push(data) => {
+                data.iter().try_for_each(|item| self.validate(item))
+            }
+            Map(data) => data.iter().try_for_each(|(key, value)| {
+                self.validate(key)?;
+                self.validate(value)
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn inner_write<O: Output>(
+        &self,
+        output: &mut O,
+        item: &RespTypeRef<'a>,
+    ) -> Result<(), EncodeError> {
+        use RespTypeRef::*;
+
+        match item {
+            // `validate` rejected these unless they fall back to bulk types
+            SimpleString(data) if !is_line_safe(data) => {
+                self.inner_write(output, &BulkString(data))?;
+            }
+            Error(data) if !is_line_safe(data) => {
+                self.inner_write(output, &BulkError(data))?;
+            }
             SimpleString(data) => {
                 output.write_all(b"+")?;
                 output.write_all(data)?;
@@ -51,12 +121,502 @@ impl<'a> Formatter<'a> {
                 }
             }
             NullArray => output.write_all(b"*-1\r\n")?,
+            Null => output.write_all(b"_\r\n")?,
+            Boolean(true) => output.write_all(b"#t\r\n")?,
+            Boolean(false) => output.write_all(b"#f\r\n")?,
+            Double(data) => {
+                output.write_all(b",")?;
+                output.write_all(format_double(*data).as_bytes())?;
+                output.write_all(b"\r\n")?;
+            }
+            BigNumber(data) => {
+                output.write_all(b"(")?;
+                output.write_all(data)?;
+                output.write_all(b"\r\n")?;
+            }
+            BulkError(data) => {
+                output.write_all(b"!")?;
+                output.write_all(data.len().to_string().as_bytes())?;
+                output.write_all(b"\r\n")?;
+                output.write_all(data)?;
+                output.write_all(b"\r\n")?;
+            }
+            VerbatimString(format, data) => {
+                output.write_all(b"=")?;
+                output.write_all((data.len() + 4).to_string().as_bytes())?;
+                output.write_all(b"\r\n")?;
+                output.write_all(format)?;
+                output.write_all(b":")?;
+                output.write_all(data)?;
+                output.write_all(b"\r\n")?;
+            }
+            Map(data) => {
+                output.write_all(b"%")?;
+                output.write_all(data.len().to_string().as_bytes())?;
+                output.write_all(b"\r\n")?;
+                for (key, value) in data {
+                    self.inner_write(output, key)?;
+                    self.inner_write(output, value)?;
+                }
+            }
+            Set(data) => {
+                output.write_all(b"~")?;
+                output.write_all(data.len().to_string().as_bytes())?;
+                output.write_all(b"\r\n")?;
+                for set_item in data {
+                    self.inner_write(output, set_item)?;
+                }
+            }
+            Push(data) => {
+                output.write_all(b">")?;
+                output.write_all(data.len().to_string().as_bytes())?;
+                output.write_all(b"\r\n")?;
+                for push_item in data {
+                    self.inner_write(output, push_item)?;
+                }
+            }
+            StreamedString(chunks) => {
+                output.write_all(b"$?\r\n")?;
+                // an empty chunk would end the string early
+                for chunk in chunks.iter().filter(|x| !x.is_empty()) {
+                    output.write_all(b";")?;
+                    output.write_all(chunk.len().to_string().as_bytes())?;
+                    output.write_all(b"\r\n")?;
+                    output.write_all(chunk)?;
+                    output.write_all(b"\r\n")?;
+                }
+                output.write_all(b";0\r\n")?;
+            }
         };
 
         Ok(())
     }
 }
 
+// destination of `Formatter`, a `Vec<u8>` or with `std` any `io::Write`
+trait Output {
+    fn write_all(&mut self, data: &[u8]) -> Result<(), EncodeError>;
+}
+
+impl Output for Vec<u8> {
+    fn write_all(&mut self, data: &[u8]) -> Result<(), EncodeError> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+struct IoOutput<'w, W>(&'w mut W);
+
+#[cfg(feature = "std")]
+impl<W: Write> Output for IoOutput<'_, W> {
+    fn write_all(&mut self, data: &[u8]) -> Result<(), EncodeError> {
+        Ok(self.0.write_all(data)?)
+    }
+}
+
+fn is_line_safe(data: &[u8]) -> bool {
+    memchr::memchr2(b'\r', b'\n', data).is_none()
+}
+
+fn format_double(data: f64) -> String {
+    if data.is_nan() {
+        String::from("nan")
+    } else if data.is_infinite() && data > 0.0 {
+        String::from("inf")
+    } else if data.is_infinite() {
+        String::from("-inf")
+    } else {
+        data.to_string()
+    }
+}
+
+/// Writes a RESP3 streamed string (`$?`) for payloads whose length is not known up front.
+#[cfg(feature = "std")]
+pub struct StreamedStringWriter<'w, W: Write> {
+    output: &'w mut W,
+}
+
+#[cfg(feature = "std")]
+impl<'w, W: Write> StreamedStringWriter<'w, W> {
+    pub fn new(output: &'w mut W) -> std::io::Result<StreamedStringWriter<'w, W>> {
+        output.write_all(b"$?\r\n")?;
+        Ok(StreamedStringWriter { output })
+    }
+
+    pub fn write_chunk(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        // an empty chunk would terminate the string
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        self.output.write_all(b";")?;
+        self.output.write_all(chunk.len().to_string().as_bytes())?;
+        self.output.write_all(b"\r\n")?;
+        self.output.write_all(chunk)?;
+        self.output.write_all(b"\r\n")
+    }
+
+    pub fn finish(self) -> std::io::Result<()> {
+        self.output.write_all(b";0\r\n")
+    }
+}
+
+#[cfg(feature = "std")]
+/// Writes a RESP3 streamed aggregate (`*?`, `~?` or `%?`) element by element.
+pub struct StreamedAggregateWriter<'w, W: Write> {
+    output: &'w mut W,
+    options: FormatterOptions,
+}
+
+#[cfg(feature = "std")]
+impl<'w, W: Write> StreamedAggregateWriter<'w, W> {
+    pub fn array(output: &'w mut W) -> Result<StreamedAggregateWriter<'w, W>, EncodeError> {
+        Self::start(output, b"*?\r\n")
+    }
+
+    pub fn set(output: &'w mut W) -> Result<StreamedAggregateWriter<'w, W>, EncodeError> {
+        Self::start(output, b"~?\r\n")
+    }
+
+    pub fn map(output: &'w mut W) -> Result<StreamedAggregateWriter<'w, W>, EncodeError> {
+        Self::start(output, b"%?\r\n")
+    }
+
+    fn start(
+        output: &'w mut W,
+        header: &[u8],
+    ) -> Result<StreamedAggregateWriter<'w, W>, EncodeError> {
+        output.write_all(header)?;
+        Ok(StreamedAggregateWriter {
+            output,
+            options: FormatterOptions::default(),
+        })
+    }
+
+    pub fn set_options(&mut self, options: FormatterOptions) {
+        self.options = options;
+    }
+
+    pub fn push(&mut self, item: RespTypeRef<'_>) -> Result<(), EncodeError> {
+        Formatter::new(item, self.options).try_write(self.output)
+    }
+
+    pub fn push_entry(
+        &mut self,
+        key: RespTypeRef<'_>,
+        value: RespTypeRef<'_>,
+    ) -> Result<(), EncodeError> {
+        self.push(key)?;
+        self.push(value)
+    }
+
+    pub fn finish(self) -> Result<(), EncodeError> {
+        Ok(self.output.write_all(b".\r\n")?)
+    }
+}
+
+#[derive(Debug)]
+pub enum EncodeError {
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    /// Number of elements declared in the aggregate header and the number actually written,
+    /// keys and values of a map are counted separately.
+    CountMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    UnsafeSimpleString,
+    UnsafeError,
+    /// A nested writer was dropped without a successful `finish`, so the element it started
+    /// is incomplete.
+    Unfinished,
+    /// A map entry count whose keys and values together do not fit in a `usize`.
+    InvalidSize,
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        match self {
+            #[cfg(feature = "std")]
+            EncodeError::Io(error) => write!(f, "{}", error),
+            EncodeError::CountMismatch { expected, actual } => write!(
+                f,
+                "aggregate declared {} elements but {} were written",
+                expected, actual
+            ),
+            EncodeError::UnsafeSimpleString => {
+                write!(f, "simple string contains a carriage return or newline")
+            }
+            EncodeError::UnsafeError => write!(f, "error contains a carriage return or newline"),
+            EncodeError::Unfinished => write!(f, "a nested aggregate was not finished"),
+            EncodeError::InvalidSize => write!(f, "map entry count is too large"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EncodeError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for EncodeError {
+    fn from(error: std::io::Error) -> Self {
+        EncodeError::Io(error)
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct Progress {
+    written: usize,
+    // set while a nested aggregate is written, still set afterwards if it was not finished
+    nested: bool,
+}
+
+#[cfg(feature = "std")]
+struct Aggregate<'w, W: Write> {
+    output: &'w mut W,
+    expected: usize,
+    progress: Progress,
+    // a nested aggregate only counts in its parent once it was finished
+    parent: Option<&'w mut Progress>,
+    options: FormatterOptions,
+}
+
+#[cfg(feature = "std")]
+impl<'w, W: Write> Aggregate<'w, W> {
+    fn start(
+        output: &'w mut W,
+        prefix: &[u8],
+        count: usize,
+        expected: usize,
+    ) -> Result<Aggregate<'w, W>, EncodeError> {
+        output.write_all(prefix)?;
+        output.write_all(count.to_string().as_bytes())?;
+        output.write_all(b"\r\n")?;
+
+        Ok(Aggregate {
+            output,
+            expected,
+            progress: Progress::default(),
+            parent: None,
+            options: FormatterOptions::default(),
+        })
+    }
+
+    fn check_room(&self) -> Result<(), EncodeError> {
+        if self.progress.nested {
+            return Err(EncodeError::Unfinished);
+        }
+        if self.progress.written == self.expected {
+            return Err(EncodeError::CountMismatch {
+                expected: self.expected,
+                actual: self.progress.written + 1,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn nested(
+        &mut self,
+        prefix: &[u8],
+        count: usize,
+        expected: usize,
+    ) -> Result<Aggregate<'_, W>, EncodeError> {
+        self.check_room()?;
+
+        let options = self.options;
+        let Aggregate {
+            output, progress, ..
+        } = self;
+        progress.nested = true;
+        let mut nested = Aggregate::start(&mut **output, prefix, count, expected)?;
+        nested.parent = Some(progress);
+        nested.options = options;
+
+        Ok(nested)
+    }
+
+    fn push(&mut self, item: RespTypeRef<'_>) -> Result<(), EncodeError> {
+        self.check_room()?;
+        Formatter::new(item, self.options).try_write(self.output)?;
+        self.progress.written += 1;
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), EncodeError> {
+        if self.progress.nested {
+            return Err(EncodeError::Unfinished);
+        }
+        if self.progress.written != self.expected {
+            return Err(EncodeError::CountMismatch {
+                expected: self.expected,
+                actual: self.progress.written,
+            });
+        }
+
+        if let Some(parent) = self.parent {
+            parent.nested = false;
+            parent.written += 1;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+/// Writes an array, set or push element by element after declaring the element count, so
+/// replies can be encoded straight from an iterator. `finish` must be called to check the count.
+#[must_use = "call `finish` to check the element count"]
+pub struct ArrayWriter<'w, W: Write> {
+    inner: Aggregate<'w, W>,
+}
+
+#[cfg(feature = "std")]
+impl<'w, W: Write> ArrayWriter<'w, W> {
+    pub fn new(output: &'w mut W, count: usize) -> Result<ArrayWriter<'w, W>, EncodeError> {
+        Self::start(output, b"*", count)
+    }
+
+    pub fn new_set(output: &'w mut W, count: usize) -> Result<ArrayWriter<'w, W>, EncodeError> {
+        Self::start(output, b"~", count)
+    }
+
+    pub fn new_push(output: &'w mut W, count: usize) -> Result<ArrayWriter<'w, W>, EncodeError> {
+        Self::start(output, b">", count)
+    }
+
+    fn start(
+        output: &'w mut W,
+        prefix: &[u8],
+        count: usize,
+    ) -> Result<ArrayWriter<'w, W>, EncodeError> {
+        Ok(ArrayWriter {
+            inner: Aggregate::start(output, prefix, count, count)?,
+        })
+    }
+
+    pub fn set_options(&mut self, options: FormatterOptions) {
+        self.inner.options = options;
+    }
+
+    pub fn push(&mut self, item: RespTypeRef<'_>) -> Result<(), EncodeError> {
+        self.inner.push(item)
+    }
+
+    pub fn array(&mut self, count: usize) -> Result<ArrayWriter<'_, W>, EncodeError> {
+        Ok(ArrayWriter {
+            inner: self.inner.nested(b"*", count, count)?,
+        })
+    }
+
+    pub fn set(&mut self, count: usize) -> Result<ArrayWriter<'_, W>, EncodeError> {
+        Ok(ArrayWriter {
+            inner: self.inner.nested(b"~", count, count)?,
+        })
+    }
+
+    pub fn map(&mut self, count: usize) -> Result<MapWriter<'_, W>, EncodeError> {
+        Ok(MapWriter {
+            inner: self.inner.nested(b"%", count, map_len(count)?)?,
+        })
+    }
+
+    pub fn finish(self) -> Result<(), EncodeError> {
+        self.inner.finish()
+    }
+}
+
+#[cfg(feature = "std")]
+/// Writes a map entry by entry after declaring the entry count, `push`, `array`, `set` and `map`
+/// alternate between writing a key and writing its value.
+#[must_use = "call `finish` to check the element count"]
+pub struct MapWriter<'w, W: Write> {
+    inner: Aggregate<'w, W>,
+}
+
+#[cfg(feature = "std")]
+impl<'w, W: Write> MapWriter<'w, W> {
+    pub fn new(output: &'w mut W, count: usize) -> Result<MapWriter<'w, W>, EncodeError> {
+        Ok(MapWriter {
+            inner: Aggregate::start(output, b"%", count, map_len(count)?)?,
+        })
+    }
+
+    pub fn push_entry(
+        &mut self,
+        key: RespTypeRef<'_>,
+        value: RespTypeRef<'_>,
+    ) -> Result<(), EncodeError> {
+        self.inner.push(key)?;
+        self.inner.push(value)
+    }
+
+    pub fn set_options(&mut self, options: FormatterOptions) {
+        self.inner.options = options;
+    }
+
+    pub fn push(&mut self, item: RespTypeRef<'_>) -> Result<(), EncodeError> {
+        self.inner.push(item)
+    }
+
+    pub fn array(&mut self, count: usize) -> Result<ArrayWriter<'_, W>, EncodeError> {
+        Ok(ArrayWriter {
+            inner: self.inner.nested(b"*", count, count)?,
+        })
+    }
+
+    pub fn set(&mut self, count: usize) -> Result<ArrayWriter<'_, W>, EncodeError> {
+        Ok(ArrayWriter {
+            inner: self.inner.nested(b"~", count, count)?,
+        })
+    }
+
+    pub fn map(&mut self, count: usize) -> Result<MapWriter<'_, W>, EncodeError> {
+        Ok(MapWriter {
+            inner: self.inner.nested(b"%", count, map_len(count)?)?,
+        })
+    }
+
+    pub fn finish(self) -> Result<(), EncodeError> {
+        self.inner.finish()
+    }
+}
+
+#[cfg(feature = "std")]
+/// Writes a bulk string of `length` bytes copied from `source`, without loading the payload
+/// in memory. Fails with `UnexpectedEof` when `source` ends before `length` bytes are copied.
+pub fn write_bulk_string_from_reader<W: Write, R: Read>(
+    output: &mut W,
+    length: u64,
+    source: R,
+) -> std::io::Result<()> {
+    output.write_all(b"$")?;
+    output.write_all(length.to_string().as_bytes())?;
+    output.write_all(b"\r\n")?;
+
+    let copied = std::io::copy(&mut source.take(length), output)?;
+    if copied != length {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("source ended after {} of {} bytes", copied, length),
+        ));
+    }
+
+    output.write_all(b"\r\n")?;
+
+    Ok(())
+}
+
+// keys and values are counted separately
+#[cfg(feature = "std")]
+fn map_len(count: usize) -> Result<usize, EncodeError> {
+    count.checked_mul(2).ok_or(EncodeError::InvalidSize)
+}
+
+#[cfg(feature = "std")]
 #[test]
 fn formatter_simple_string() {
     let formatter = Formatter::new_with_defaults(RespTypeRef::SimpleString(b"just text"));
@@ -69,6 +629,7 @@ fn formatter_simple_string() {
     assert_eq!(buffer, expected);
 }
 
+#[cfg(feature = "std")]
 #[test]
 fn formatter_error() {
     let formatter = Formatter::new_with_defaults(RespTypeRef::Error(b"CRASH"));
@@ -81,6 +642,7 @@ fn formatter_error() {
     assert_eq!(buffer, expected);
 }
 
+#[cfg(feature = "std")]
 #[test]
 fn formatter_integer() {
     let formatter = Formatter::new_with_defaults(RespTypeRef::Integer(12345));
@@ -93,6 +655,7 @@ fn formatter_integer() {
     assert_eq!(buffer, expected);
 }
 
+#[cfg(feature = "std")]
 #[test]
 fn formatter_bulk_string() {
     let formatter = Formatter::new_with_defaults(RespTypeRef::BulkString(b"Just some text"));
@@ -105,6 +668,7 @@ fn formatter_bulk_string() {
     assert_eq!(buffer, expected);
 }
 
+#[cfg(feature = "std")]
 #[test]
 fn formatter_integer_array() {
     let formatter = Formatter::new_with_defaults(RespTypeRef::Array(vec![
@@ -121,6 +685,7 @@ fn formatter_integer_array() {
     assert_eq!(buffer, expected);
 }
 
+#[cfg(feature = "std")]
 #[test]
 fn formatter_mixed_array() {
     let formatter = Formatter::new_with_defaults(RespTypeRef::Array(vec![
@@ -136,3 +701,382 @@ fn formatter_mixed_array() {
 
     assert_eq!(buffer, expected);
 }
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_bulk_string_from_reader() {
+    let expected = b"$14\r\nJust some text\r\n";
+    let mut buffer = Vec::new();
+
+    write_bulk_string_from_reader(&mut buffer, 14, &b"Just some text and more"[..]).unwrap();
+
+    assert_eq!(buffer, expected);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_bulk_string_from_short_reader() {
+    let mut buffer = Vec::new();
+
+    let error = write_bulk_string_from_reader(&mut buffer, 14, &b"Just"[..]).unwrap_err();
+
+    assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_resp3_simple_types() {
+    let formatter = Formatter::new_with_defaults(RespTypeRef::Array(vec![
+        RespTypeRef::Null,
+        RespTypeRef::Boolean(true),
+        RespTypeRef::Double(1.5),
+        RespTypeRef::Double(f64::INFINITY),
+        RespTypeRef::BigNumber(b"-12345678901234567890"),
+    ]));
+    let expected = b"*5\r\n_\r\n#t\r\n,1.5\r\n,inf\r\n(-12345678901234567890\r\n";
+    let mut buffer = Vec::new();
+
+    formatter.write(&mut buffer).unwrap();
+
+    assert_eq!(buffer, expected);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_resp3_blob_types() {
+    let formatter = Formatter::new_with_defaults(RespTypeRef::Push(vec![
+        RespTypeRef::BulkError(b"SYNTAX invalid"),
+        RespTypeRef::VerbatimString(*b"txt", b"Some string"),
+    ]));
+    let expected = b">2\r\n!14\r\nSYNTAX invalid\r\n=15\r\ntxt:Some string\r\n";
+    let mut buffer = Vec::new();
+
+    formatter.write(&mut buffer).unwrap();
+
+    assert_eq!(buffer, expected);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_resp3_aggregates() {
+    let formatter = Formatter::new_with_defaults(RespTypeRef::Map(vec![(
+        RespTypeRef::SimpleString(b"key"),
+        RespTypeRef::Set(vec![RespTypeRef::Integer(1)]),
+    )]));
+    let expected = b"%1\r\n+key\r\n~1\r\n:1\r\n";
+    let mut buffer = Vec::new();
+
+    formatter.write(&mut buffer).unwrap();
+
+    assert_eq!(buffer, expected);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_streamed_string() {
+    let formatter =
+        Formatter::new_with_defaults(RespTypeRef::StreamedString(vec![b"Hell", b"", b"o world"]));
+    let expected = b"$?\r\n;4\r\nHell\r\n;7\r\no world\r\n;0\r\n";
+    let mut buffer = Vec::new();
+
+    formatter.write(&mut buffer).unwrap();
+
+    assert_eq!(buffer, expected);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_streamed_aggregate_writer() {
+    let mut buffer = Vec::new();
+
+    let mut writer = StreamedAggregateWriter::map(&mut buffer).unwrap();
+    writer
+        .push_entry(RespTypeRef::SimpleString(b"a"), RespTypeRef::Integer(1))
+        .unwrap();
+    writer.finish().unwrap();
+
+    let mut writer = StreamedAggregateWriter::array(&mut buffer).unwrap();
+    writer.push(RespTypeRef::Boolean(false)).unwrap();
+    writer.finish().unwrap();
+
+    assert_eq!(buffer, b"%?\r\n+a\r\n:1\r\n.\r\n*?\r\n#f\r\n.\r\n");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_streamed_output_round_trip() {
+    let mut buffer = Vec::new();
+
+    let mut writer = StreamedAggregateWriter::set(&mut buffer).unwrap();
+    writer
+        .push(RespTypeRef::StreamedString(vec![b"ab", b"\r\n"]))
+        .unwrap();
+    writer.push(RespTypeRef::Double(-0.5)).unwrap();
+    writer.finish().unwrap();
+
+    assert_eq!(
+        crate::bytes_to_resp_type(&buffer).unwrap(),
+        crate::RespType::Set(vec![
+            crate::RespType::BulkString(b"ab\r\n".to_vec()),
+            crate::RespType::Double(-0.5)
+        ])
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_array_writer() {
+    let mut buffer = Vec::new();
+
+    let mut writer = ArrayWriter::new(&mut buffer, 3).unwrap();
+    for value in 1..=2 {
+        writer.push(RespTypeRef::Integer(value)).unwrap();
+    }
+    let mut nested = writer.array(2).unwrap();
+    nested.push(RespTypeRef::SimpleString(b"OK")).unwrap();
+    nested.push(RespTypeRef::NullString).unwrap();
+    nested.finish().unwrap();
+    writer.finish().unwrap();
+
+    assert_eq!(buffer, b"*3\r\n:1\r\n:2\r\n*2\r\n+OK\r\n$-1\r\n");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_map_writer() {
+    let mut buffer = Vec::new();
+
+    let mut writer = MapWriter::new(&mut buffer, 2).unwrap();
+    writer
+        .push_entry(RespTypeRef::SimpleString(b"a"), RespTypeRef::Integer(1))
+        .unwrap();
+    writer.push(RespTypeRef::SimpleString(b"b")).unwrap();
+    let mut value = writer.map(1).unwrap();
+    value
+        .push_entry(RespTypeRef::Integer(2), RespTypeRef::Boolean(true))
+        .unwrap();
+    value.finish().unwrap();
+    writer.finish().unwrap();
+
+    assert_eq!(buffer, b"%2\r\n+a\r\n:1\r\n+b\r\n%1\r\n:2\r\n#t\r\n");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_array_writer_set_and_push() {
+    let mut buffer = Vec::new();
+
+    let mut writer = ArrayWriter::new_push(&mut buffer, 2).unwrap();
+    writer.push(RespTypeRef::SimpleString(b"message")).unwrap();
+    let mut set = writer.set(1).unwrap();
+    set.push(RespTypeRef::Integer(1)).unwrap();
+    set.finish().unwrap();
+    writer.finish().unwrap();
+
+    assert_eq!(buffer, b">2\r\n+message\r\n~1\r\n:1\r\n");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_array_writer_too_few_elements() {
+    let mut buffer = Vec::new();
+
+    let mut writer = ArrayWriter::new(&mut buffer, 2).unwrap();
+    writer.push(RespTypeRef::Integer(1)).unwrap();
+
+    match writer.finish() {
+        Err(EncodeError::CountMismatch { expected, actual }) => {
+            assert_eq!((expected, actual), (2, 1))
+        }
+        _ => panic!("expected a count mismatch"),
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_map_writer_too_many_elements() {
+    let mut buffer = Vec::new();
+
+    let mut writer = MapWriter::new(&mut buffer, 1).unwrap();
+    writer
+        .push_entry(RespTypeRef::Integer(1), RespTypeRef::Integer(2))
+        .unwrap();
+
+    match writer.push(RespTypeRef::Integer(3)) {
+        Err(EncodeError::CountMismatch { expected, actual }) => {
+            assert_eq!((expected, actual), (2, 3))
+        }
+        _ => panic!("expected a count mismatch"),
+    }
+    assert_eq!(buffer, b"%1\r\n:1\r\n:2\r\n");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_nested_writer_counts_when_finished() {
+    let mut buffer = Vec::new();
+
+    // a nested writer that was dropped makes the parent fail
+    let mut writer = ArrayWriter::new(&mut buffer, 2).unwrap();
+    let mut nested = writer.array(2).unwrap();
+    nested.push(RespTypeRef::Integer(1)).unwrap();
+    drop(nested);
+    assert!(matches!(
+        writer.push(RespTypeRef::Integer(2)),
+        Err(EncodeError::Unfinished)
+    ));
+    assert!(matches!(writer.finish(), Err(EncodeError::Unfinished)));
+
+    // as does one whose `finish` failed
+    let mut writer = MapWriter::new(&mut buffer, 1).unwrap();
+    writer.push(RespTypeRef::Integer(1)).unwrap();
+    let nested = writer.set(1).unwrap();
+    assert!(nested.finish().is_err());
+    assert!(matches!(writer.finish(), Err(EncodeError::Unfinished)));
+
+    // a finished one counts once
+    let mut writer = ArrayWriter::new(&mut buffer, 1).unwrap();
+    let nested = writer.array(0).unwrap();
+    nested.finish().unwrap();
+    assert!(matches!(
+        writer.array(0).map(|_| ()),
+        Err(EncodeError::CountMismatch {
+            expected: 1,
+            actual: 2
+        })
+    ));
+    writer.finish().unwrap();
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_rejects_unsafe_simple_string() {
+    let formatter = Formatter::new_with_defaults(RespTypeRef::SimpleString(b"two\r\nlines"));
+    let mut buffer = Vec::new();
+
+    assert!(matches!(
+        formatter.try_write(&mut buffer),
+        Err(EncodeError::UnsafeSimpleString)
+    ));
+    assert_eq!(
+        formatter.write(&mut buffer).unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_rejects_unsafe_error() {
+    let formatter = Formatter::new_with_defaults(RespTypeRef::Error(b"ERR bad\nkey"));
+    let mut buffer = Vec::new();
+
+    assert!(matches!(
+        formatter.try_write(&mut buffer),
+        Err(EncodeError::UnsafeError)
+    ));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_rejects_before_writing() {
+    let formatter = Formatter::new_with_defaults(RespTypeRef::Array(vec![
+        RespTypeRef::Integer(1),
+        RespTypeRef::Map(vec![(
+            RespTypeRef::SimpleString(b"key"),
+            RespTypeRef::Error(b"a\nb"),
+        )]),
+    ]));
+    let mut buffer = Vec::new();
+
+    assert!(formatter.try_write(&mut buffer).is_err());
+    assert!(formatter.write_to_vec(&mut buffer).is_err());
+    assert!(buffer.is_empty());
+
+    // a rejected element is not counted
+    let mut writer = ArrayWriter::new(&mut buffer, 1).unwrap();
+    assert!(writer.push(RespTypeRef::SimpleString(b"\r")).is_err());
+    writer.push(RespTypeRef::SimpleString(b"OK")).unwrap();
+    writer.finish().unwrap();
+    assert_eq!(buffer, b"*1\r\n+OK\r\n");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_fallback_to_bulk() {
+    let options = FormatterOptions {
+        fallback_to_bulk: true,
+    };
+    let formatter = Formatter::new(
+        RespTypeRef::Array(vec![
+            RespTypeRef::SimpleString(b"a\rb"),
+            RespTypeRef::Error(b"ERR a\nb"),
+            RespTypeRef::SimpleString(b"OK"),
+        ]),
+        options,
+    );
+    let expected = b"*3\r\n$3\r\na\rb\r\n!7\r\nERR a\nb\r\n+OK\r\n";
+    let mut buffer = Vec::new();
+
+    formatter.write(&mut buffer).unwrap();
+
+    assert_eq!(buffer, expected);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_writer_options_are_inherited() {
+    let mut buffer = Vec::new();
+
+    let mut writer = ArrayWriter::new(&mut buffer, 1).unwrap();
+    writer.set_options(FormatterOptions {
+        fallback_to_bulk: true,
+    });
+    let mut nested = writer.map(1).unwrap();
+    nested
+        .push_entry(RespTypeRef::SimpleString(b"k"), RespTypeRef::Error(b"\n"))
+        .unwrap();
+    nested.finish().unwrap();
+    writer.finish().unwrap();
+
+    assert_eq!(buffer, b"*1\r\n%1\r\n+k\r\n!1\r\n\n\r\n");
+}
+
+#[test]
+fn formatter_write_to_vec() {
+    let formatter = Formatter::new_with_defaults(RespTypeRef::Array(vec![
+        RespTypeRef::SimpleString(b"OK"),
+        RespTypeRef::StreamedString(vec![b"ab", b"", b"c"]),
+    ]));
+
+    let mut output = Vec::new();
+    formatter.write_to_vec(&mut output).unwrap();
+    assert_eq!(output, b"*2\r\n+OK\r\n$?\r\n;2\r\nab\r\n;1\r\nc\r\n;0\r\n");
+
+    #[cfg(feature = "std")]
+    {
+        let mut written = Vec::new();
+        formatter.write(&mut written).unwrap();
+        assert_eq!(output, written);
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn formatter_map_writer_count_overflow() {
+    let mut buffer = Vec::new();
+
+    assert!(matches!(
+        MapWriter::new(&mut buffer, usize::MAX),
+        Err(EncodeError::InvalidSize)
+    ));
+    assert!(buffer.is_empty());
+
+    let mut writer = ArrayWriter::new(&mut buffer, 1).unwrap();
+    assert!(matches!(
+        writer.map(usize::MAX),
+        Err(EncodeError::InvalidSize)
+    ));
+    writer.push(RespTypeRef::Integer(1)).unwrap();
+    writer.finish().unwrap();
+    assert_eq!(buffer, b"*1\r\n:1\r\n");
+}
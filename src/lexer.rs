@@ -1,4 +1,5 @@
-use memchr::memmem;
+#[cfg(test)]
+use alloc::vec::Vec;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenType {
@@ -13,9 +14,76 @@ pub enum TokenType {
     BulkString,
     ArrayStart,
     ArraySize,
+    NullStart,
+    BooleanStart,
+    Boolean,
+    DoubleStart,
+    Double,
+    BigNumberStart,
+    BigNumber,
+    BulkErrorStart,
+    BulkErrorSize,
+    BulkError,
+    VerbatimStringStart,
+    VerbatimStringSize,
+    VerbatimString,
+    MapStart,
+    MapSize,
+    SetStart,
+    SetSize,
+    PushStart,
+    PushSize,
+    ChunkStart,
+    ChunkSize,
+    Chunk,
+    StreamEnd,
     Newline,
 }
 
+impl TokenType {
+    fn line_content(self) -> Option<TokenType> {
+        use TokenType::*;
+
+        match self {
+            SimpleStringStart => Some(SimpleString),
+            ErrorStart => Some(Error),
+            IntegerStart => Some(Integer),
+            BooleanStart => Some(Boolean),
+            DoubleStart => Some(Double),
+            BigNumberStart => Some(BigNumber),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> Option<TokenType> {
+        use TokenType::*;
+
+        match self {
+            BulkStringStart => Some(BulkStringSize),
+            ArrayStart => Some(ArraySize),
+            BulkErrorStart => Some(BulkErrorSize),
+            VerbatimStringStart => Some(VerbatimStringSize),
+            MapStart => Some(MapSize),
+            SetStart => Some(SetSize),
+            PushStart => Some(PushSize),
+            ChunkStart => Some(ChunkSize),
+            _ => None,
+        }
+    }
+
+    fn payload(self) -> Option<TokenType> {
+        use TokenType::*;
+
+        match self {
+            BulkStringSize => Some(BulkString),
+            BulkErrorSize => Some(BulkError),
+            VerbatimStringSize => Some(VerbatimString),
+            ChunkSize => Some(Chunk),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Token<'a> {
     pub start: usize,
@@ -24,76 +92,89 @@ pub struct Token<'a> {
     pub tokentype: TokenType,
 }
 
-fn find_newline(input: &[u8]) -> Option<usize> {
-    memmem::find(input, b"\r\n")
+// a plain `memchr` for `\r` is much cheaper than setting up a `memmem` searcher per line
+pub(crate) fn find_newline(input: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+
+    while let Some(found) = memchr::memchr(b'\r', &input[offset..]) {
+        let index = offset + found;
+        if input.get(index + 1) == Some(&b'\n') {
+            return Some(index);
+        }
+        offset = index + 1;
+    }
+
+    None
+}
+
+pub(crate) fn parse_size(input: &[u8]) -> Option<usize> {
+    core::str::from_utf8(input).ok()?.parse().ok()
+}
+
+fn take_line(input: &[u8], tokentype: TokenType) -> (usize, Option<TokenType>) {
+    if let Some(found) = find_newline(input) {
+        (found, Some(tokentype))
+    } else {
+        (0, None)
+    }
 }
 
 impl<'a> Token<'a> {
-    fn take(input: &'a [u8], previous: &Option<TokenType>) -> (usize, Option<TokenType>) {
+    fn take(
+        input: &'a [u8],
+        previous: &Option<TokenType>,
+        payload_size: Option<usize>,
+    ) -> (usize, Option<TokenType>) {
         use TokenType::*;
 
-        if input.len() == 0 {
+        if input.is_empty() {
             return (0, None);
         };
 
-        if input.len() >= 2 {
-            if &input[0..=1] == b"\r\n" {
-                return (2, Some(Newline));
+        // bulk payloads are binary safe, so take the declared size instead of searching for a newline
+        if let Some(size) = payload_size {
+            if size > 0 && input.len() >= size {
+                return (size, previous.and_then(TokenType::payload));
             }
         };
 
-        match input[0] {
-            _ if previous == &Some(TokenType::SimpleStringStart) => {
-                if let Some(found) = find_newline(input) {
-                    (found, Some(SimpleString))
-                } else {
-                    (0, None)
-                }
-            }
-            _ if previous == &Some(TokenType::ErrorStart) => {
-                if let Some(found) = find_newline(input) {
-                    (found, Some(Error))
-                } else {
-                    (0, None)
-                }
-            }
-            _ if previous == &Some(TokenType::IntegerStart) => {
-                if let Some(found) = find_newline(input) {
-                    (found, Some(Integer))
-                } else {
-                    (0, None)
-                }
-            }
-            x if previous == &Some(TokenType::BulkStringStart)
-                && ((b'0'..b'9').contains(&x) || x == b'-') =>
-            {
-                if let Some(found) = find_newline(input) {
-                    (found, Some(BulkStringSize))
-                } else {
-                    (0, None)
-                }
+        if input.len() >= 2 && &input[0..=1] == b"\r\n" {
+            return (2, Some(Newline));
+        };
+
+        if let Some(previous) = previous {
+            if let Some(content) = previous.line_content() {
+                return take_line(input, content);
             }
-            x if previous == &Some(TokenType::ArrayStart)
-                && ((b'0'..b'9').contains(&x) || x == b'-') =>
-            {
-                if let Some(found) = find_newline(input) {
-                    (found, Some(ArraySize))
-                } else {
-                    (0, None)
+
+            if let Some(size) = previous.size() {
+                if input[0].is_ascii_digit() || input[0] == b'-' || input[0] == b'?' {
+                    return take_line(input, size);
                 }
             }
-            _ if previous == &Some(TokenType::BulkStringSize) => {
-                if let Some(found) = find_newline(input) {
-                    (found, Some(BulkString))
-                } else {
-                    (0, None)
-                }
+
+            if let Some(payload) = previous.payload() {
+                return take_line(input, payload);
             }
+        }
+
+        match input[0] {
             b'+' => (1, Some(SimpleStringStart)),
             b'-' => (1, Some(ErrorStart)),
             b':' => (1, Some(IntegerStart)),
             b'$' => (1, Some(BulkStringStart)),
             b'*' => (1, Some(ArrayStart)),
+            b'_' => (1, Some(NullStart)),
+            b'#' => (1, Some(BooleanStart)),
+            b',' => (1, Some(DoubleStart)),
+            b'(' => (1, Some(BigNumberStart)),
+            b'!' => (1, Some(BulkErrorStart)),
+            b'=' => (1, Some(VerbatimStringStart)),
+            b'%' => (1, Some(MapStart)),
+            b'~' => (1, Some(SetStart)),
+            b'>' => (1, Some(PushStart)),
+            b';' => (1, Some(ChunkStart)),
+            b'.' => (1, Some(StreamEnd)),
             _ => (0, None),
         }
     }
@@ -103,6 +184,8 @@ pub struct Lexer<'a> {
     data: &'a [u8],
     start: usize,
     previous: Option<TokenType>,
+    declared_size: Option<usize>,
+    payload_size: Option<usize>,
 }
 
 impl<'a> Lexer<'a> {
@@ -111,17 +194,35 @@ impl<'a> Lexer<'a> {
             data,
             start: 0,
             previous: None,
+            declared_size: None,
+            payload_size: None,
+        }
+    }
+
+    // continues at `start`, which has to be the beginning of a value
+    pub(crate) fn resume(data: &'a [u8], start: usize) -> Self {
+        Lexer {
+            start,
+            ..Lexer::new(data)
         }
     }
+
+    pub fn position(&self) -> usize {
+        self.start
+    }
+
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.start..]
+    }
 }
 
 impl<'a> Iterator for Lexer<'a> {
     type Item = Token<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // dbg!(std::str::from_utf8(&self.data[self.start..]));
+        // dbg!(core::str::from_utf8(&self.data[self.start..]));
 
-        match Token::take(&self.data[self.start..], &self.previous) {
+        match Token::take(&self.data[self.start..], &self.previous, self.payload_size) {
             (_, None) => None,
             (length, Some(tokentype)) => {
                 let end = self.start + length;
@@ -134,8 +235,21 @@ impl<'a> Iterator for Lexer<'a> {
                 };
 
                 self.start = end;
-                if tokentype != TokenType::Newline {
+                if tokentype == TokenType::Newline {
+                    // only a size line that announced a payload keeps its state past the newline
+                    self.payload_size = self.declared_size.take();
+                    if self.payload_size.is_none() {
+                        self.previous = None;
+                    }
+                } else {
                     self.previous = Some(tokentype);
+                    self.payload_size = None;
+                    self.declared_size = match tokentype {
+                        // the terminating `;0` chunk has no payload and no trailing newline
+                        TokenType::ChunkSize => parse_size(data).filter(|size| *size > 0),
+                        x if x.payload().is_some() => parse_size(data),
+                        _ => None,
+                    };
                 }
 
                 Some(token)
@@ -485,3 +599,70 @@ fn lexer_test_9() {
         ]
     );
 }
+
+#[test]
+fn lexer_test_10() {
+    let tokenizer = Lexer::new(b"$4\r\n\r\nab\r\n");
+    let tokens: Vec<_> = tokenizer.collect();
+
+    assert_eq!(
+        tokens,
+        vec![
+            Token {
+                start: 0,
+                end: 1,
+                data: b"$",
+                tokentype: TokenType::BulkStringStart
+            },
+            Token {
+                start: 1,
+                end: 2,
+                data: b"4",
+                tokentype: TokenType::BulkStringSize
+            },
+            Token {
+                start: 2,
+                end: 4,
+                data: b"\r\n",
+                tokentype: TokenType::Newline
+            },
+            Token {
+                start: 4,
+                end: 8,
+                data: b"\r\nab",
+                tokentype: TokenType::BulkString
+            },
+            Token {
+                start: 8,
+                end: 10,
+                data: b"\r\n",
+                tokentype: TokenType::Newline
+            }
+        ]
+    );
+}
+
+#[test]
+fn lexer_test_11() {
+    let tokenizer = Lexer::new(b"$?\r\n;2\r\nab\r\n;0\r\n.\r\n");
+    let tokens: Vec<_> = tokenizer.map(|token| token.tokentype).collect();
+
+    assert_eq!(
+        tokens,
+        vec![
+            TokenType::BulkStringStart,
+            TokenType::BulkStringSize,
+            TokenType::Newline,
+            TokenType::ChunkStart,
+            TokenType::ChunkSize,
+            TokenType::Newline,
+            TokenType::Chunk,
+            TokenType::Newline,
+            TokenType::ChunkStart,
+            TokenType::ChunkSize,
+            TokenType::Newline,
+            TokenType::StreamEnd,
+            TokenType::Newline,
+        ]
+    );
+}
@@ -1,23 +1,92 @@
-/// In RESP, the first byte determines the data type:
-/// For Simple Strings, the first byte of the reply is "+"
-/// For Errors, the first byte of the reply is "-"
-/// For Integers, the first byte of the reply is ":"
-/// For Bulk Strings, the first byte of the reply is "$"
-/// For Arrays, the first byte of the reply is "*"
-///
-///
-///
+//! In RESP, the first byte determines the data type:
+//! For Simple Strings, the first byte of the reply is "+"
+//! For Errors, the first byte of the reply is "-"
+//! For Integers, the first byte of the reply is ":"
+//! For Bulk Strings, the first byte of the reply is "$"
+//! For Arrays, the first byte of the reply is "*"
+//!
+//! RESP3 adds Null "_", Booleans "#", Doubles ",", Big numbers "(", Bulk errors "!",
+//! Verbatim strings "=", Maps "%", Sets "~" and Pushes ">", and allows "?" as the size of
+//! streamed strings and aggregates.
+//!
+//! Without the default `std` feature the crate only needs `alloc`, the `Decoder` and the
+//! `io::Write` based writers are left out and `Formatter::write_to_vec` is used instead.
+//! The `tokio` feature adds `AsyncConnection`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg_attr(test, macro_use)]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod aof;
+#[cfg(feature = "tokio")]
+pub mod async_connection;
+pub mod command;
+#[cfg(feature = "std")]
+pub mod connection;
+pub mod connection_info;
+#[cfg(feature = "std")]
+pub mod connection_pool;
+#[cfg(feature = "std")]
+pub mod decoder;
+#[cfg(feature = "std")]
+pub mod dump;
 pub mod formatter;
+pub mod handshake;
 pub mod lexer;
+#[cfg(feature = "rayon")]
+pub mod parallel;
 pub mod parser;
+pub mod pool;
+pub mod pubsub;
+#[cfg(feature = "std")]
+pub mod rdb;
+#[cfg(feature = "std")]
+pub mod replication;
+pub mod resp;
 pub mod resp_type;
+pub mod scanner;
+pub mod spanned;
+pub mod tape;
+pub mod tracking;
 pub mod value;
 
-use std::fmt::Display;
+#[cfg(test)]
+use alloc::string::ToString;
+use core::fmt::Display;
 
+#[cfg(feature = "std")]
+pub use aof::{AofReader, AofWriter};
+#[cfg(feature = "tokio")]
+pub use async_connection::AsyncConnection;
+pub use command::Command;
+#[cfg(feature = "std")]
+pub use connection::Connection;
+pub use connection_info::{Address, ConnectionInfo, Protocol};
+#[cfg(feature = "tokio")]
+pub use connection_pool::AsyncConnectionPool;
+#[cfg(feature = "std")]
+pub use connection_pool::{ConnectionPool, PoolOptions};
+#[cfg(feature = "std")]
+pub use decoder::Decoder;
+#[cfg(feature = "std")]
+pub use dump::DumpPayload;
+pub use handshake::{Handshake, HandshakeError, HandshakeStep, ServerInfo};
 pub use lexer::Lexer;
-pub use parser::Parser;
+pub use parser::{ParseMode, Parser, ParserOptions};
+pub use pool::BufferPool;
+pub use pubsub::{PubSubError, PubSubEvent};
+#[cfg(feature = "std")]
+pub use rdb::{RdbEvent, RdbReader, RdbRecord, RdbValue};
+#[cfg(feature = "std")]
+pub use replication::{ReplicationDecoder, ReplicationEvent};
+pub use resp::Resp;
 pub use resp_type::{RespType, RespTypeRef};
+pub use scanner::{frame_len, frame_len_with_options, split_frames};
+pub use spanned::{Spanned, SpannedNode};
+pub use tape::{Cursor, Tape};
+pub use tracking::{Invalidation, LocalCache};
 pub use value::Value;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -29,6 +98,11 @@ pub enum RespErrorType {
     InvalidData,
     InvalidInteger,
     InvalidSize,
+    UnexpectedSign,
+    LeadingZero,
+    InvalidLineCharacter,
+    TrailingData,
+    LimitExceeded,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -37,24 +111,43 @@ pub struct ParseError<'a> {
     error_type: RespErrorType,
 }
 
+impl<'a> ParseError<'a> {
+    pub fn error_type(&self) -> RespErrorType {
+        self.error_type
+    }
+
+    pub fn token(&self) -> Option<&lexer::Token<'a>> {
+        self.token.as_ref()
+    }
+}
+
 impl<'a> Display for ParseError<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
         write!(f, "{:?}", self.error_type)
     }
 }
 
+#[cfg(feature = "std")]
 impl<'a> std::error::Error for ParseError<'a> {}
 
-pub fn bytes_to_value(data: &[u8]) -> Result<Result<Value, Value>, ParseError> {
+pub fn bytes_to_value(data: &[u8]) -> Result<Result<Value, Value>, ParseError<'_>> {
     Ok(bytes_to_resp_type(data)?.into_value())
 }
 
-pub fn bytes_to_resp_type(data: &[u8]) -> Result<RespType, ParseError> {
-    Ok(Parser::new_from_bytes(data.as_ref()).parse()?.to_owned())
+pub fn bytes_to_resp_type(data: &[u8]) -> Result<RespType, ParseError<'_>> {
+    Ok(Parser::new_from_bytes(data).parse()?.to_owned())
+}
+
+pub fn bytes_to_resp_type_ref<'a>(data: &'a [u8]) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+    Parser::new_from_bytes(data).parse()
+}
+
+pub fn bytes_to_resp<'a>(data: &'a [u8]) -> Result<Resp<'a>, ParseError<'a>> {
+    Parser::new_from_bytes(data).parse_resp()
 }
 
-pub fn bytes_to_resp_type_ref<'a>(data: &'a [u8]) -> Result<RespTypeRef<'a>, ParseError> {
-    Ok(Parser::new_from_bytes(data.as_ref()).parse()?)
+pub fn bytes_to_spanned<'a>(data: &'a [u8]) -> Result<Spanned<'a>, ParseError<'a>> {
+    Parser::new_from_bytes(data).parse_spanned()
 }
 
 #[test]
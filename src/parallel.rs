@@ -0,0 +1,81 @@
+use rayon::prelude::*;
+
+use crate::scanner::split_frames;
+use crate::{Lexer, ParseError, Parser, ParserOptions, RespType};
+
+/// Parses all complete frames of a pipelined buffer in parallel, in their original order.
+/// Returns the frames and the number of bytes they span, the rest of `data` is an incomplete
+/// frame that should be parsed again once more data is available.
+pub fn parse_frames(data: &[u8]) -> Result<(Vec<RespType>, usize), ParseError<'_>> {
+    parse_frames_with_options(data, ParserOptions::default())
+}
+
+pub fn parse_frames_with_options(
+    data: &[u8],
+    options: ParserOptions,
+) -> Result<(Vec<RespType>, usize), ParseError<'_>> {
+    let frames = split_frames(data, options)?;
+    let consumed = frames.last().map_or(0, |frame| frame.end);
+
+    let results: Vec<_> = frames
+        .into_par_iter()
+        .map(|frame| {
+            Parser::new_with_options(Lexer::new(&data[frame.clone()]), options)
+                .parse()
+                .map(|item| item.to_owned())
+                .map_err(|mut error| {
+                    // relative to `data` instead of the frame
+                    if let Some(token) = &mut error.token {
+                        token.start += frame.start;
+                        token.end += frame.start;
+                    }
+                    error
+                })
+        })
+        .collect();
+
+    // the error of the first invalid frame, whichever thread found it
+    let items = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    Ok((items, consumed))
+}
+
+#[test]
+fn parse_frames_in_order() {
+    let mut data = Vec::new();
+    for i in 0..1000 {
+        data.extend_from_slice(format!("*2\r\n:{}\r\n$3\r\nfoo\r\n", i).as_bytes());
+    }
+    data.extend_from_slice(b"$5\r\nhel");
+
+    let (items, consumed) = parse_frames(&data).unwrap();
+
+    assert_eq!(items.len(), 1000);
+    assert_eq!(consumed, data.len() - 7);
+    for (i, item) in items.into_iter().enumerate() {
+        assert_eq!(
+            item,
+            RespType::Array(vec![
+                RespType::Integer(i as i64),
+                RespType::BulkString(b"foo".to_vec())
+            ])
+        );
+    }
+}
+
+#[test]
+fn parse_frames_error() {
+    let error = parse_frames(b"+OK\r\n:1\r\n:x\r\n").unwrap_err();
+
+    assert_eq!(error.error_type(), crate::RespErrorType::InvalidInteger);
+    assert_eq!(error.token().unwrap().start, 10);
+
+    let mut data = Vec::new();
+    for _ in 0..1000 {
+        data.extend_from_slice(b"+OK\r\n");
+    }
+    data.extend_from_slice(b"#x\r\n+OK\r\n#y\r\n");
+    let error = parse_frames(&data).unwrap_err();
+    assert_eq!(error.token().unwrap().start, 5001);
+    assert_eq!(error.token().unwrap().data, b"x");
+}
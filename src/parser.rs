@@ -1,9 +1,133 @@
+#[cfg(test)]
+use alloc::string::String;
+use alloc::vec::Vec;
+
 use crate::lexer::{Token, TokenType};
+use crate::spanned::{Spanned, SpannedNode};
+use crate::tape::{Node, NodeKind, Tape};
 use crate::Lexer;
-use crate::{ParseError, RespErrorType, RespTypeRef};
+use crate::{ParseError, Resp, RespErrorType, RespTypeRef};
+
+/// `Strict` enforces the exact Redis grammar, `Lenient` accepts numbers with a leading `+`,
+/// leading zeros or surrounding whitespace and ignores bytes after a complete frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+/// Limits are `None` by default. `max_aggregate_len` counts entries for maps and applies to
+/// the number of items in streamed aggregates as well, `max_bulk_len` also applies to the
+/// joined chunks of streamed strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParserOptions {
+    pub mode: ParseMode,
+    pub max_depth: Option<usize>,
+    pub max_bulk_len: Option<usize>,
+    pub max_aggregate_len: Option<usize>,
+}
+
+impl ParserOptions {
+    pub(crate) fn exceeds_size_limit(&self, token_type: TokenType, size: i64) -> bool {
+        let limit = match token_type {
+            TokenType::BulkStringSize
+            | TokenType::BulkErrorSize
+            | TokenType::VerbatimStringSize
+            | TokenType::ChunkSize => self.max_bulk_len,
+            TokenType::ArraySize
+            | TokenType::SetSize
+            | TokenType::PushSize
+            | TokenType::MapSize => self.max_aggregate_len,
+            _ => None,
+        };
+
+        limit.is_some_and(|max| size > max as i64)
+    }
+
+    pub(crate) fn exceeds_aggregate_len(&self, len: usize) -> bool {
+        self.max_aggregate_len.is_some_and(|max| len > max)
+    }
+}
+
+fn parse_number(token: Token<'_>, mode: ParseMode) -> Result<i64, ParseError<'_>> {
+    let data = match mode {
+        ParseMode::Strict => {
+            check_strict_number(&token)?;
+            token.data
+        }
+        ParseMode::Lenient => token.data.trim_ascii(),
+    };
+
+    match core::str::from_utf8(data).ok().and_then(|x| x.parse().ok()) {
+        Some(number) => Ok(number),
+        None => Err(ParseError {
+            error_type: RespErrorType::InvalidInteger,
+            token: Some(token),
+        }),
+    }
+}
+
+fn check_strict_number<'a>(token: &Token<'a>) -> Result<(), ParseError<'a>> {
+    let digits = token.data.strip_prefix(b"-").unwrap_or(token.data);
+
+    let error_type = if token.data.first() == Some(&b'+') || token.data == b"-0" {
+        RespErrorType::UnexpectedSign
+    } else if digits.is_empty() || !digits.iter().all(|x| x.is_ascii_digit()) {
+        RespErrorType::InvalidInteger
+    } else if digits.len() > 1 && digits[0] == b'0' {
+        RespErrorType::LeadingZero
+    } else {
+        return Ok(());
+    };
+
+    Err(ParseError {
+        error_type,
+        token: Some(token.clone()),
+    })
+}
+
+// Size of a bulk or aggregate header, shared with the scanner so `frame_len` and `parse`
+// accept the same frames. Only bulk strings and arrays can be null.
+pub(crate) fn parse_size<'a>(
+    token: Token<'a>,
+    options: &ParserOptions,
+) -> Result<i64, ParseError<'a>> {
+    let size = parse_number(token.clone(), options.mode)?;
+    let nullable = matches!(
+        token.tokentype,
+        TokenType::BulkStringSize | TokenType::ArraySize
+    );
+    // maps count pairs, the number of values has to fit as well
+    let too_large = token.tokentype == TokenType::MapSize && size.checked_mul(2).is_none();
+
+    if size < -1 || (size == -1 && !nullable) || too_large {
+        return Err(ParseError {
+            error_type: RespErrorType::InvalidSize,
+            token: Some(token),
+        });
+    }
+
+    if options.exceeds_size_limit(token.tokentype, size) {
+        return Err(ParseError {
+            error_type: RespErrorType::LimitExceeded,
+            token: Some(token),
+        });
+    }
+
+    Ok(size)
+}
 
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
+    options: ParserOptions,
+    depth: usize,
+}
+
+enum Item<'a, T> {
+    Value(T),
+    // `.` that closes a streamed aggregate
+    End(Token<'a>),
 }
 
 impl<'a> Parser<'a> {
@@ -12,211 +136,828 @@ impl<'a> Parser<'a> {
     }
 
     pub fn new(lexer: Lexer<'a>) -> Parser<'a> {
-        Parser { lexer }
+        Parser::new_with_options(lexer, ParserOptions::default())
+    }
+
+    pub fn new_with_options(lexer: Lexer<'a>, options: ParserOptions) -> Parser<'a> {
+        Parser {
+            lexer,
+            options,
+            depth: 0,
+        }
+    }
+
+    pub fn options(&self) -> ParserOptions {
+        self.options
+    }
+
+    /// Starts over on new input, keeping the options.
+    pub fn reset(&mut self, data: &'a [u8]) {
+        self.lexer = Lexer::new(data);
+        self.depth = 0;
+    }
+
+    /// Parses a single frame that should span the whole input, in strict mode any bytes after
+    /// the frame are reported as `TrailingData`.
+    pub fn parse_complete(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        let item = self.parse()?;
+
+        if self.options.mode == ParseMode::Strict && !self.lexer.remaining().is_empty() {
+            return Err(ParseError {
+                error_type: RespErrorType::TrailingData,
+                token: self.lexer.next(),
+            });
+        }
+
+        Ok(item)
     }
 
     pub fn parse(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        match self.parse_item()? {
+            Item::Value(item) => Ok(item),
+            Item::End(token) => Err(ParseError {
+                error_type: RespErrorType::InvalidStart,
+                token: Some(token),
+            }),
+        }
+    }
+
+    /// Like `parse`, but every node of the returned tree carries its byte ranges in the input.
+    pub fn parse_spanned(&mut self) -> Result<Spanned<'a>, ParseError<'a>> {
+        match self.parse_spanned_item()? {
+            Item::Value(item) => Ok(item),
+            Item::End(token) => Err(ParseError {
+                error_type: RespErrorType::InvalidStart,
+                token: Some(token),
+            }),
+        }
+    }
+
+    /// Like `parse`, but builds a `Resp` that borrows from the input. Only streamed strings
+    /// with more than one chunk are copied.
+    pub fn parse_resp(&mut self) -> Result<Resp<'a>, ParseError<'a>> {
+        match self.parse_resp_item()? {
+            Item::Value(item) => Ok(item),
+            Item::End(token) => Err(ParseError {
+                error_type: RespErrorType::InvalidStart,
+                token: Some(token),
+            }),
+        }
+    }
+
+    /// Like `parse`, but stores all nodes in a single `Tape` instead of a tree.
+    pub fn parse_tape(&mut self) -> Result<Tape<'a>, ParseError<'a>> {
+        self.parse_tape_with(Vec::new())
+    }
+
+    /// Like `parse_tape`, but fills `nodes` so the buffer of an earlier `Tape` can be reused,
+    /// see `Tape::into_nodes`.
+    pub fn parse_tape_with(&mut self, mut nodes: Vec<Node>) -> Result<Tape<'a>, ParseError<'a>> {
+        let input_start = self.lexer.position();
+        let input = self.lexer.remaining();
+        nodes.clear();
+        self.parse_tape_value(&mut nodes, input_start)?;
+
+        Ok(Tape::new(input, nodes))
+    }
+
+    fn next_start(&mut self) -> Result<Token<'a>, ParseError<'a>> {
         match self.lexer.next() {
-            Some(token) if token.tokentype == TokenType::SimpleStringStart => {
-                return self.parse_simple_string()
-            }
-            Some(token) if token.tokentype == TokenType::ErrorStart => return self.parse_error(),
-            Some(token) if token.tokentype == TokenType::IntegerStart => {
-                return self.parse_integer()
+            Some(token) => Ok(token),
+            None => Err(ParseError {
+                error_type: RespErrorType::InvalidStart,
+                token: None,
+            }),
+        }
+    }
+
+    fn parse_item(&mut self) -> Result<Item<'a, RespTypeRef<'a>>, ParseError<'a>> {
+        let token = self.next_start()?;
+        self.parse_after_start(token)
+    }
+
+    fn parse_after_start(
+        &mut self,
+        token: Token<'a>,
+    ) -> Result<Item<'a, RespTypeRef<'a>>, ParseError<'a>> {
+        let item = match token.tokentype {
+            TokenType::SimpleStringStart => self.parse_simple_string()?,
+            TokenType::ErrorStart => self.parse_error()?,
+            TokenType::IntegerStart => self.parse_integer()?,
+            TokenType::BulkStringStart => self.parse_bulk_string()?,
+            TokenType::ArrayStart => self.nested(&token, Self::parse_array)?,
+            TokenType::NullStart => {
+                self.check_newline()?;
+                RespTypeRef::Null
             }
-            Some(token) if token.tokentype == TokenType::BulkStringStart => {
-                return self.parse_bulk_string()
+            TokenType::BooleanStart => self.parse_boolean()?,
+            TokenType::DoubleStart => self.parse_double()?,
+            TokenType::BigNumberStart => self.parse_big_number()?,
+            TokenType::BulkErrorStart => self.parse_bulk_error()?,
+            TokenType::VerbatimStringStart => self.parse_verbatim_string()?,
+            TokenType::MapStart => self.nested(&token, Self::parse_map)?,
+            TokenType::SetStart => self.nested(&token, Self::parse_set)?,
+            TokenType::PushStart => self.nested(&token, Self::parse_push)?,
+            TokenType::StreamEnd => {
+                self.check_newline()?;
+                return Ok(Item::End(token));
             }
-            Some(token) if token.tokentype == TokenType::ArrayStart => return self.parse_array(),
-            Some(token) => {
+            _ => {
                 return Err(ParseError {
                     error_type: RespErrorType::InvalidStart,
                     token: Some(token),
                 })
             }
-            None => {
-                return Err(ParseError {
-                    error_type: RespErrorType::InvalidStart,
-                    token: None,
-                })
+        };
+
+        Ok(Item::Value(item))
+    }
+
+    fn parse_spanned_item(&mut self) -> Result<Item<'a, Spanned<'a>>, ParseError<'a>> {
+        let start = self.lexer.position();
+        let token = self.next_start()?;
+
+        let size_type = match token.tokentype {
+            TokenType::ArrayStart => TokenType::ArraySize,
+            TokenType::SetStart => TokenType::SetSize,
+            TokenType::PushStart => TokenType::PushSize,
+            TokenType::MapStart => TokenType::MapSize,
+            _ => {
+                let item = match self.parse_after_start(token)? {
+                    Item::Value(item) => item,
+                    Item::End(token) => return Ok(Item::End(token)),
+                };
+                let end = self.lexer.position();
+                let (header_end, payload_end) = leaf_ranges(&item, start, end);
+
+                return Ok(Item::Value(Spanned {
+                    frame: start..end,
+                    header: start..header_end,
+                    payload: header_end..payload_end,
+                    node: SpannedNode::Value(item),
+                }));
             }
-        }
+        };
+
+        self.nested(&token, |parser| {
+            parser.parse_spanned_aggregate(start, token.tokentype, size_type)
+        })
     }
 
-    fn parse_simple_string(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
-        match self.lexer.next() {
-            Some(Token {
-                tokentype: TokenType::SimpleString,
-                data,
-                ..
-            }) => {
-                self.check_newline()?;
-                return Ok(RespTypeRef::SimpleString(data));
+    fn parse_spanned_aggregate(
+        &mut self,
+        start: usize,
+        tokentype: TokenType,
+        size_type: TokenType,
+    ) -> Result<Item<'a, Spanned<'a>>, ParseError<'a>> {
+        let (size, size_token) = self._parse_size(size_type)?;
+        let header_end = self.lexer.position();
+        let mut payload_end = header_end;
+
+        let node = match (tokentype, size) {
+            (TokenType::ArrayStart, Some(-1)) => SpannedNode::Value(RespTypeRef::NullArray),
+            (_, Some(-1)) | (TokenType::PushStart, None) => {
+                return Err(Self::invalid_size(size_token))
             }
-            Some(token) => {
-                return Err(ParseError {
-                    error_type: RespErrorType::InvalidData,
-                    token: Some(token),
-                })
+            (TokenType::MapStart, Some(size)) => {
+                let mut pairs = Vec::new();
+                for _ in 0..size {
+                    pairs.push((self.parse_spanned()?, self.parse_spanned()?));
+                }
+                payload_end = self.lexer.position();
+                SpannedNode::Map(pairs)
             }
-            None => {
-                return Err(ParseError {
-                    error_type: RespErrorType::InvalidData,
-                    token: None,
-                })
+            (TokenType::MapStart, None) => {
+                let mut pairs = Vec::new();
+                while let Item::Value(key) = self.parse_spanned_item()? {
+                    pairs.push((key, self.parse_spanned()?));
+                    self.check_aggregate_len(pairs.len(), &size_token)?;
+                    payload_end = self.lexer.position();
+                }
+                SpannedNode::Map(pairs)
             }
-        }
+            (tokentype, size) => {
+                let mut items = Vec::new();
+                match size {
+                    Some(size) => {
+                        for _ in 0..size {
+                            items.push(self.parse_spanned()?);
+                        }
+                        payload_end = self.lexer.position();
+                    }
+                    None => {
+                        while let Item::Value(item) = self.parse_spanned_item()? {
+                            items.push(item);
+                            self.check_aggregate_len(items.len(), &size_token)?;
+                            payload_end = self.lexer.position();
+                        }
+                    }
+                }
+
+                match tokentype {
+                    TokenType::SetStart => SpannedNode::Set(items),
+                    TokenType::PushStart => SpannedNode::Push(items),
+                    _ => SpannedNode::Array(items),
+                }
+            }
+        };
+
+        Ok(Item::Value(Spanned {
+            frame: start..self.lexer.position(),
+            header: start..header_end,
+            payload: header_end..payload_end,
+            node,
+        }))
     }
 
-    fn parse_error(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
-        match self.lexer.next() {
-            Some(Token {
-                tokentype: TokenType::Error,
-                data,
-                ..
-            }) => {
-                self.check_newline()?;
-                return Ok(RespTypeRef::Error(data));
+    fn parse_resp_item(&mut self) -> Result<Item<'a, Resp<'a>>, ParseError<'a>> {
+        let token = self.next_start()?;
+
+        let size_type = match token.tokentype {
+            TokenType::ArrayStart => TokenType::ArraySize,
+            TokenType::SetStart => TokenType::SetSize,
+            TokenType::PushStart => TokenType::PushSize,
+            TokenType::MapStart => TokenType::MapSize,
+            _ => {
+                return match self.parse_after_start(token)? {
+                    Item::Value(item) => Ok(Item::Value(item.into())),
+                    Item::End(token) => Ok(Item::End(token)),
+                }
             }
-            Some(token) => {
-                return Err(ParseError {
-                    error_type: RespErrorType::InvalidData,
-                    token: Some(token),
-                })
+        };
+
+        self.nested(&token, |parser| {
+            parser.parse_resp_aggregate(token.tokentype, size_type)
+        })
+        .map(Item::Value)
+    }
+
+    fn parse_resp_aggregate(
+        &mut self,
+        tokentype: TokenType,
+        size_type: TokenType,
+    ) -> Result<Resp<'a>, ParseError<'a>> {
+        let (size, size_token) = self._parse_size(size_type)?;
+
+        match (tokentype, size) {
+            (TokenType::ArrayStart, Some(-1)) => Ok(Resp::NullArray),
+            (_, Some(-1)) | (TokenType::PushStart, None) => Err(Self::invalid_size(size_token)),
+            (TokenType::MapStart, Some(size)) => {
+                let mut pairs = Vec::new();
+                for _ in 0..size {
+                    pairs.push((self.parse_resp()?, self.parse_resp()?));
+                }
+                Ok(Resp::Map(pairs))
             }
-            None => {
-                return Err(ParseError {
-                    error_type: RespErrorType::InvalidData,
-                    token: None,
+            (TokenType::MapStart, None) => {
+                let mut pairs = Vec::new();
+                while let Item::Value(key) = self.parse_resp_item()? {
+                    pairs.push((key, self.parse_resp()?));
+                    self.check_aggregate_len(pairs.len(), &size_token)?;
+                }
+                Ok(Resp::Map(pairs))
+            }
+            (tokentype, size) => {
+                let mut items = Vec::new();
+                match size {
+                    Some(size) => {
+                        for _ in 0..size {
+                            items.push(self.parse_resp()?);
+                        }
+                    }
+                    None => {
+                        while let Item::Value(item) = self.parse_resp_item()? {
+                            items.push(item);
+                            self.check_aggregate_len(items.len(), &size_token)?;
+                        }
+                    }
+                }
+
+                Ok(match tokentype {
+                    TokenType::SetStart => Resp::Set(items),
+                    TokenType::PushStart => Resp::Push(items),
+                    _ => Resp::Array(items),
                 })
             }
         }
     }
 
-    fn parse_integer(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
-        match self.lexer.next() {
-            Some(token) if token.tokentype == TokenType::Integer => {
-                self.check_newline()?;
+    // Returns false for the `.` that closes a streamed aggregate. Node offsets are relative
+    // to `input_start`.
+    fn parse_tape_item(
+        &mut self,
+        nodes: &mut Vec<Node>,
+        input_start: usize,
+    ) -> Result<Item<'a, ()>, ParseError<'a>> {
+        let start = self.lexer.position();
+        let base = self.lexer.remaining().as_ptr() as usize - (start - input_start);
+        let token = self.next_start()?;
+        let index = nodes.len();
 
-                let integer = Self::_parse_integer_bytes(token.data).map_err(|_| ParseError {
-                    error_type: RespErrorType::InvalidInteger,
-                    token: Some(token),
-                })?;
+        let (kind, size_type) = match token.tokentype {
+            TokenType::ArrayStart => (NodeKind::Array, TokenType::ArraySize),
+            TokenType::SetStart => (NodeKind::Set, TokenType::SetSize),
+            TokenType::PushStart => (NodeKind::Push, TokenType::PushSize),
+            TokenType::MapStart => (NodeKind::Map, TokenType::MapSize),
+            _ => {
+                let item = match self.parse_after_start(token)? {
+                    Item::Value(item) => item,
+                    Item::End(token) => return Ok(Item::End(token)),
+                };
+                let end = self.lexer.position();
+                let (header_end, payload_end) = leaf_ranges(&item, start, end);
+                let mut payload = header_end - input_start..payload_end - input_start;
+
+                let kind = match item {
+                    RespTypeRef::SimpleString(_) => NodeKind::SimpleString,
+                    RespTypeRef::Error(_) => NodeKind::Error,
+                    RespTypeRef::Integer(x) => NodeKind::Integer(x),
+                    RespTypeRef::BulkString(_) => NodeKind::BulkString,
+                    RespTypeRef::NullString => NodeKind::NullString,
+                    RespTypeRef::Null => NodeKind::Null,
+                    RespTypeRef::Boolean(x) => NodeKind::Boolean(x),
+                    RespTypeRef::Double(x) => NodeKind::Double(x),
+                    RespTypeRef::BigNumber(_) => NodeKind::BigNumber,
+                    RespTypeRef::BulkError(_) => NodeKind::BulkError,
+                    RespTypeRef::VerbatimString(format, _) => {
+                        // skip the `txt:` prefix
+                        payload.start += 4;
+                        NodeKind::VerbatimString(format)
+                    }
+                    RespTypeRef::StreamedString(chunks) => {
+                        nodes.push(Node {
+                            kind: NodeKind::StreamedString,
+                            payload,
+                            len: chunks.len(),
+                            next: index + chunks.len() + 1,
+                        });
+                        for chunk in chunks {
+                            let offset = chunk.as_ptr() as usize - base;
+                            nodes.push(Node {
+                                kind: NodeKind::Chunk,
+                                payload: offset..offset + chunk.len(),
+                                len: 0,
+                                next: nodes.len() + 1,
+                            });
+                        }
+                        return Ok(Item::Value(()));
+                    }
+                    // aggregates are handled above
+                    _ => unreachable!(),
+                };
 
-                return Ok(RespTypeRef::Integer(integer));
+                nodes.push(Node {
+                    kind,
+                    payload,
+                    len: 0,
+                    next: index + 1,
+                });
+                return Ok(Item::Value(()));
             }
-            Some(token) => {
-                return Err(ParseError {
-                    error_type: RespErrorType::InvalidData,
-                    token: Some(token),
-                })
+        };
+
+        self.nested(&token, |parser| {
+            parser.parse_tape_aggregate(nodes, input_start, token.tokentype, kind, size_type)
+        })?;
+
+        Ok(Item::Value(()))
+    }
+
+    fn parse_tape_aggregate(
+        &mut self,
+        nodes: &mut Vec<Node>,
+        input_start: usize,
+        tokentype: TokenType,
+        kind: NodeKind,
+        size_type: TokenType,
+    ) -> Result<(), ParseError<'a>> {
+        let (size, size_token) = self._parse_size(size_type)?;
+        let header_end = self.lexer.position() - input_start;
+        let index = nodes.len();
+
+        let per_entry = match (tokentype, size) {
+            (TokenType::ArrayStart, Some(-1)) => {
+                nodes.push(Node {
+                    kind: NodeKind::NullArray,
+                    payload: header_end..header_end,
+                    len: 0,
+                    next: index + 1,
+                });
+                return Ok(());
+            }
+            (_, Some(-1)) | (TokenType::PushStart, None) => {
+                return Err(Self::invalid_size(size_token))
+            }
+            (TokenType::MapStart, _) => 2,
+            _ => 1,
+        };
+
+        nodes.push(Node {
+            kind,
+            payload: header_end..header_end,
+            len: 0,
+            next: 0,
+        });
+
+        let mut len = 0;
+        let mut payload_end = header_end;
+        match size {
+            Some(size) => {
+                let Some(values) = size.checked_mul(per_entry) else {
+                    return Err(Self::invalid_size(size_token));
+                };
+                let values = values as usize;
+                // the size comes from the peer, every value takes at least 3 bytes of input
+                nodes.reserve(values.min(self.lexer.remaining().len() / 3));
+                for _ in 0..values {
+                    self.parse_tape_value(nodes, input_start)?;
+                }
+                len = values;
+                payload_end = self.lexer.position() - input_start;
             }
             None => {
-                return Err(ParseError {
-                    error_type: RespErrorType::InvalidData,
-                    token: None,
-                })
+                while let Item::Value(()) = self.parse_tape_item(nodes, input_start)? {
+                    for _ in 1..per_entry {
+                        self.parse_tape_value(nodes, input_start)?;
+                    }
+                    len += per_entry as usize;
+                    self.check_aggregate_len(len / per_entry as usize, &size_token)?;
+                    payload_end = self.lexer.position() - input_start;
+                }
             }
         }
+
+        let next = nodes.len();
+        let node = &mut nodes[index];
+        node.payload.end = payload_end;
+        node.len = len;
+        node.next = next;
+
+        Ok(())
+    }
+
+    fn parse_tape_value(
+        &mut self,
+        nodes: &mut Vec<Node>,
+        input_start: usize,
+    ) -> Result<(), ParseError<'a>> {
+        match self.parse_tape_item(nodes, input_start)? {
+            Item::Value(()) => Ok(()),
+            Item::End(token) => Err(ParseError {
+                error_type: RespErrorType::InvalidStart,
+                token: Some(token),
+            }),
+        }
+    }
+
+    fn parse_simple_string(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        let token = self._parse_line(TokenType::SimpleString)?;
+        Ok(RespTypeRef::SimpleString(token.data))
+    }
+
+    fn parse_error(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        let token = self._parse_line(TokenType::Error)?;
+        Ok(RespTypeRef::Error(token.data))
     }
 
-    fn _parse_integer_bytes(data: &[u8]) -> Result<i64, Box<dyn std::error::Error>> {
-        let str_data = std::str::from_utf8(data)?;
-        let int = str_data.parse()?;
-        Ok(int)
+    fn parse_integer(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        let token = self._parse_line(TokenType::Integer)?;
+        let integer = self._parse_number(token)?;
+
+        Ok(RespTypeRef::Integer(integer))
+    }
+
+    fn _parse_number(&self, token: Token<'a>) -> Result<i64, ParseError<'a>> {
+        parse_number(token, self.options.mode)
+    }
+
+    fn parse_boolean(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        let token = self._parse_line(TokenType::Boolean)?;
+
+        match token.data {
+            b"t" => Ok(RespTypeRef::Boolean(true)),
+            b"f" => Ok(RespTypeRef::Boolean(false)),
+            _ => Err(ParseError {
+                error_type: RespErrorType::InvalidData,
+                token: Some(token),
+            }),
+        }
+    }
+
+    fn parse_double(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        let token = self._parse_line(TokenType::Double)?;
+        let data = match self.options.mode {
+            ParseMode::Strict => Some(token.data).filter(|x| is_double_literal(x)),
+            ParseMode::Lenient => Some(token.data.trim_ascii()),
+        };
+
+        match data.map(|x| core::str::from_utf8(x).map(|x| x.parse())) {
+            Some(Ok(Ok(double))) => Ok(RespTypeRef::Double(double)),
+            _ => Err(ParseError {
+                error_type: RespErrorType::InvalidData,
+                token: Some(token),
+            }),
+        }
+    }
+
+    fn parse_big_number(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        let token = self._parse_line(TokenType::BigNumber)?;
+        let digits = token.data.strip_prefix(b"-").unwrap_or(token.data);
+
+        if digits.is_empty() || !digits.iter().all(|x| x.is_ascii_digit()) {
+            return Err(ParseError {
+                error_type: RespErrorType::InvalidInteger,
+                token: Some(token),
+            });
+        }
+
+        Ok(RespTypeRef::BigNumber(token.data))
     }
 
     fn parse_bulk_string(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
-        let size = self.parse_bulk_string_size()?;
-        if size == -1 {
-            return Ok(RespTypeRef::NullString);
+        match self._parse_size(TokenType::BulkStringSize)? {
+            (Some(-1), _) => Ok(RespTypeRef::NullString),
+            (Some(size), _) => Ok(RespTypeRef::BulkString(
+                self._parse_payload(size, TokenType::BulkString)?,
+            )),
+            (None, _) => self.parse_streamed_string(),
         }
+    }
 
-        match self.lexer.next() {
-            Some(token) if token.tokentype == TokenType::BulkString => {
-                self.check_newline()?;
+    fn parse_streamed_string(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        let mut chunks = Vec::new();
+        let mut total = 0;
 
-                if token.data.len() != size.try_into().unwrap() {
+        loop {
+            match self.lexer.next() {
+                Some(token) if token.tokentype == TokenType::ChunkStart => {
+                    match self._parse_size(TokenType::ChunkSize)? {
+                        (Some(0), _) => return Ok(RespTypeRef::StreamedString(chunks)),
+                        (Some(size), token) if size > 0 => {
+                            total += size as usize;
+                            if self.options.max_bulk_len.is_some_and(|max| total > max) {
+                                return Err(Self::limit_exceeded(token));
+                            }
+                            chunks.push(self._parse_payload(size, TokenType::Chunk)?)
+                        }
+                        (_, token) => return Err(Self::invalid_size(token)),
+                    }
+                }
+                Some(token) => {
                     return Err(ParseError {
-                        error_type: RespErrorType::InvalidSize,
+                        error_type: RespErrorType::InvalidData,
                         token: Some(token),
-                    });
+                    })
+                }
+                None => {
+                    return Err(ParseError {
+                        error_type: RespErrorType::InvalidData,
+                        token: None,
+                    })
                 }
-
-                return Ok(RespTypeRef::BulkString(token.data));
-            }
-            Some(token) if token.tokentype == TokenType::Newline && size == 0 => {
-                return Ok(RespTypeRef::BulkString(b""));
-            }
-            Some(token) => {
-                return Err(ParseError {
-                    error_type: RespErrorType::InvalidData,
-                    token: Some(token),
-                })
-            }
-            None => {
-                return Err(ParseError {
-                    error_type: RespErrorType::InvalidData,
-                    token: None,
-                })
             }
         }
     }
 
-    fn parse_bulk_string_size(&mut self) -> Result<i64, ParseError<'a>> {
-        self._parse_size(TokenType::BulkStringSize)
+    fn parse_bulk_error(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        match self._parse_size(TokenType::BulkErrorSize)? {
+            (Some(size), _) if size >= 0 => Ok(RespTypeRef::BulkError(
+                self._parse_payload(size, TokenType::BulkError)?,
+            )),
+            (_, token) => Err(Self::invalid_size(token)),
+        }
+    }
+
+    fn parse_verbatim_string(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        let size = match self._parse_size(TokenType::VerbatimStringSize)? {
+            (Some(size), _) if size >= 0 => size,
+            (_, token) => return Err(Self::invalid_size(token)),
+        };
+
+        match self.lexer.next() {
+            Some(token) if token.tokentype == TokenType::VerbatimString => {
+                self.check_newline()?;
+
+                if token.data.len() != size as usize {
+                    return Err(Self::invalid_size(token));
+                }
+
+                match token.data {
+                    [a, b, c, b':', data @ ..] => {
+                        Ok(RespTypeRef::VerbatimString([*a, *b, *c], data))
+                    }
+                    _ => Err(ParseError {
+                        error_type: RespErrorType::InvalidData,
+                        token: Some(token),
+                    }),
+                }
+            }
+            Some(token) => Err(ParseError {
+                error_type: RespErrorType::InvalidData,
+                token: Some(token),
+            }),
+            None => Err(ParseError {
+                error_type: RespErrorType::InvalidData,
+                token: None,
+            }),
+        }
     }
 
     fn parse_array(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
-        let size = self.parse_array_size()?;
-        if size == -1 {
-            return Ok(RespTypeRef::NullArray);
+        match self._parse_size(TokenType::ArraySize)? {
+            (Some(-1), _) => Ok(RespTypeRef::NullArray),
+            (size, token) => Ok(RespTypeRef::Array(self._parse_items(size, &token)?)),
         }
-        let mut array: Vec<_> = Vec::new();
+    }
+
+    fn parse_set(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        match self._parse_size(TokenType::SetSize)? {
+            (Some(-1), token) => Err(Self::invalid_size(token)),
+            (size, token) => Ok(RespTypeRef::Set(self._parse_items(size, &token)?)),
+        }
+    }
 
-        for _ in 0..size {
-            let item = self.parse()?;
-            array.push(item)
+    fn parse_push(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        match self._parse_size(TokenType::PushSize)? {
+            (Some(size), token) if size >= 0 => {
+                Ok(RespTypeRef::Push(self._parse_items(Some(size), &token)?))
+            }
+            (_, token) => Err(Self::invalid_size(token)),
+        }
+    }
+
+    fn parse_map(&mut self) -> Result<RespTypeRef<'a>, ParseError<'a>> {
+        let mut pairs = Vec::new();
+
+        match self._parse_size(TokenType::MapSize)? {
+            (Some(-1), token) => return Err(Self::invalid_size(token)),
+            (Some(size), _) => {
+                for _ in 0..size {
+                    pairs.push((self.parse()?, self.parse()?));
+                }
+            }
+            (None, token) => {
+                while let Item::Value(key) = self.parse_item()? {
+                    pairs.push((key, self.parse()?));
+                    self.check_aggregate_len(pairs.len(), &token)?;
+                }
+            }
         }
 
-        Ok(RespTypeRef::Array(array))
+        Ok(RespTypeRef::Map(pairs))
     }
 
-    fn parse_array_size(&mut self) -> Result<i64, ParseError<'a>> {
-        self._parse_size(TokenType::ArraySize)
+    // `None` reads a streamed aggregate up to its `.` terminator
+    fn _parse_items(
+        &mut self,
+        size: Option<i64>,
+        size_token: &Token<'a>,
+    ) -> Result<Vec<RespTypeRef<'a>>, ParseError<'a>> {
+        let mut items: Vec<_> = Vec::new();
+
+        match size {
+            Some(size) => {
+                for _ in 0..size {
+                    items.push(self.parse()?);
+                }
+            }
+            None => {
+                while let Item::Value(item) = self.parse_item()? {
+                    items.push(item);
+                    self.check_aggregate_len(items.len(), size_token)?;
+                }
+            }
+        }
+
+        Ok(items)
     }
 
-    fn _parse_size(&mut self, token_type: TokenType) -> Result<i64, ParseError<'a>> {
+    fn _parse_line(&mut self, token_type: TokenType) -> Result<Token<'a>, ParseError<'a>> {
         match self.lexer.next() {
             Some(token) if token.tokentype == token_type => {
                 self.check_newline()?;
 
-                let size = Self::_parse_integer_bytes(token.data).map_err(|_| ParseError {
-                    error_type: RespErrorType::InvalidInteger,
-                    token: Some(token.clone()),
-                })?;
-
-                if size < -1 {
+                if self.options.mode == ParseMode::Strict
+                    && memchr::memchr2(b'\r', b'\n', token.data).is_some()
+                {
                     return Err(ParseError {
-                        error_type: RespErrorType::InvalidSize,
-                        token: Some(token.clone()),
+                        error_type: RespErrorType::InvalidLineCharacter,
+                        token: Some(token),
                     });
                 }
 
-                Ok(size)
+                Ok(token)
             }
-            Some(token) => {
-                return Err(ParseError {
-                    error_type: RespErrorType::InvalidData,
-                    token: Some(token),
-                })
+            // empty line, e.g. `+\r\n`
+            Some(token) if token.tokentype == TokenType::Newline => Ok(Token {
+                start: token.start,
+                end: token.start,
+                data: b"",
+                tokentype: token_type,
+            }),
+            Some(token) => Err(ParseError {
+                error_type: RespErrorType::InvalidData,
+                token: Some(token),
+            }),
+            None => Err(ParseError {
+                error_type: RespErrorType::InvalidData,
+                token: None,
+            }),
+        }
+    }
+
+    fn _parse_payload(
+        &mut self,
+        size: i64,
+        token_type: TokenType,
+    ) -> Result<&'a [u8], ParseError<'a>> {
+        match self.lexer.next() {
+            Some(token) if token.tokentype == token_type => {
+                self.check_newline()?;
+
+                if token.data.len() != size as usize {
+                    return Err(Self::invalid_size(token));
+                }
+
+                Ok(token.data)
             }
-            None => {
-                return Err(ParseError {
-                    error_type: RespErrorType::InvalidData,
-                    token: None,
-                })
+            Some(token) if token.tokentype == TokenType::Newline && size == 0 => Ok(b""),
+            Some(token) => Err(ParseError {
+                error_type: RespErrorType::InvalidData,
+                token: Some(token),
+            }),
+            None => Err(ParseError {
+                error_type: RespErrorType::InvalidData,
+                token: None,
+            }),
+        }
+    }
+
+    // returns `None` for the `?` size of streamed types, together with the size token for errors
+    fn _parse_size(
+        &mut self,
+        token_type: TokenType,
+    ) -> Result<(Option<i64>, Token<'a>), ParseError<'a>> {
+        match self.lexer.next() {
+            Some(token) if token.tokentype == token_type => {
+                self.check_newline()?;
+
+                if token.data == b"?" {
+                    return Ok((None, token));
+                }
+
+                let size = parse_size(token.clone(), &self.options)?;
+                Ok((Some(size), token))
             }
+            Some(token) => Err(ParseError {
+                error_type: RespErrorType::InvalidData,
+                token: Some(token),
+            }),
+            None => Err(ParseError {
+                error_type: RespErrorType::InvalidData,
+                token: None,
+            }),
+        }
+    }
+
+    // parses an aggregate one level deeper, enforcing `max_depth`
+    fn nested<T>(
+        &mut self,
+        token: &Token<'a>,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError<'a>>,
+    ) -> Result<T, ParseError<'a>> {
+        if self.options.max_depth.is_some_and(|max| self.depth >= max) {
+            return Err(Self::limit_exceeded(token.clone()));
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn check_aggregate_len(
+        &self,
+        len: usize,
+        size_token: &Token<'a>,
+    ) -> Result<(), ParseError<'a>> {
+        match self.options.exceeds_aggregate_len(len) {
+            true => Err(Self::limit_exceeded(size_token.clone())),
+            false => Ok(()),
+        }
+    }
+
+    fn limit_exceeded(token: Token<'a>) -> ParseError<'a> {
+        ParseError {
+            error_type: RespErrorType::LimitExceeded,
+            token: Some(token),
+        }
+    }
+
+    fn invalid_size(token: Token<'a>) -> ParseError<'a> {
+        ParseError {
+            error_type: RespErrorType::InvalidSize,
+            token: Some(token),
         }
     }
 
@@ -225,23 +966,64 @@ impl<'a> Parser<'a> {
             Some(Token {
                 tokentype: TokenType::Newline,
                 ..
-            }) => return Ok(()),
-            Some(token) => {
-                return Err(ParseError {
-                    error_type: RespErrorType::NewLineMissing,
-                    token: Some(token),
-                })
-            }
-            None => {
-                return Err(ParseError {
-                    error_type: RespErrorType::NewLineMissing,
-                    token: None,
-                })
-            }
+            }) => Ok(()),
+            Some(token) => Err(ParseError {
+                error_type: RespErrorType::NewLineMissing,
+                token: Some(token),
+            }),
+            None => Err(ParseError {
+                error_type: RespErrorType::NewLineMissing,
+                token: None,
+            }),
         }
     }
 }
 
+// Header and payload end of a value that is not an aggregate, `start` and `end` delimit the frame.
+fn leaf_ranges(item: &RespTypeRef, start: usize, end: usize) -> (usize, usize) {
+    match item {
+        RespTypeRef::NullString => (end, end),
+        // `$?\r\n`
+        RespTypeRef::StreamedString(_) => (start + 4, end - 4),
+        RespTypeRef::BulkString(data) | RespTypeRef::BulkError(data) => {
+            (end - 2 - data.len(), end - 2)
+        }
+        // `txt:` prefix
+        RespTypeRef::VerbatimString(_, data) => (end - 6 - data.len(), end - 2),
+        _ => (start + 1, end - 2),
+    }
+}
+
+fn is_double_literal(data: &[u8]) -> bool {
+    if matches!(data, b"inf" | b"-inf" | b"nan") {
+        return true;
+    }
+
+    let unsigned = data
+        .strip_prefix(b"-")
+        .or_else(|| data.strip_prefix(b"+"))
+        .unwrap_or(data);
+    let (mantissa, exponent) = match unsigned.iter().position(|x| *x == b'e' || *x == b'E') {
+        Some(index) => (&unsigned[..index], Some(&unsigned[index + 1..])),
+        None => (unsigned, None),
+    };
+    let (integral, fractional) = match mantissa.iter().position(|x| *x == b'.') {
+        Some(index) => (&mantissa[..index], Some(&mantissa[index + 1..])),
+        None => (mantissa, None),
+    };
+    let digits = |x: &[u8]| !x.is_empty() && x.iter().all(|x| x.is_ascii_digit());
+
+    digits(integral)
+        && fractional.is_none_or(digits)
+        && exponent.is_none_or(|x| {
+            digits(
+                x.strip_prefix(b"-")
+                    .or_else(|| x.strip_prefix(b"+"))
+                    .unwrap_or(x),
+            )
+        })
+}
+
 #[test]
 fn parse_test_1() {
     let lexer = Lexer::new(b"+OK\r\n");
@@ -306,3 +1088,291 @@ fn parse_test_9() {
 
     assert_eq!(RespTypeRef::NullArray, parser.parse().unwrap())
 }
+
+#[test]
+fn parse_test_10() {
+    let mut parser =
+        Parser::new_from_bytes(b"*2\r\n$12\r\nhello\r\nworld\r\n$8\r\n*1\r\n:1\r\n\r\n");
+
+    assert_eq!(
+        RespTypeRef::Array(vec![
+            RespTypeRef::BulkString(b"hello\r\nworld"),
+            RespTypeRef::BulkString(b"*1\r\n:1\r\n")
+        ]),
+        parser.parse().unwrap()
+    )
+}
+
+#[test]
+fn parse_test_null_elements() {
+    let mut parser = Parser::new_from_bytes(b"*4\r\n$-1\r\n:1\r\n$0\r\n\r\n*-1\r\n");
+
+    assert_eq!(
+        RespTypeRef::Array(vec![
+            RespTypeRef::NullString,
+            RespTypeRef::Integer(1),
+            RespTypeRef::BulkString(b""),
+            RespTypeRef::NullArray
+        ]),
+        parser.parse().unwrap()
+    )
+}
+
+#[test]
+fn parse_test_resp3_simple_types() {
+    let mut parser = Parser::new_from_bytes(
+        b"_\r\n#t\r\n#f\r\n,1.25\r\n,-inf\r\n(3492890328409238509324850943850943825024385\r\n",
+    );
+
+    assert_eq!(RespTypeRef::Null, parser.parse().unwrap());
+    assert_eq!(RespTypeRef::Boolean(true), parser.parse().unwrap());
+    assert_eq!(RespTypeRef::Boolean(false), parser.parse().unwrap());
+    assert_eq!(RespTypeRef::Double(1.25), parser.parse().unwrap());
+    assert_eq!(
+        RespTypeRef::Double(f64::NEG_INFINITY),
+        parser.parse().unwrap()
+    );
+    assert_eq!(
+        RespTypeRef::BigNumber(b"3492890328409238509324850943850943825024385"),
+        parser.parse().unwrap()
+    );
+}
+
+#[test]
+fn parse_test_resp3_blob_types() {
+    let mut parser =
+        Parser::new_from_bytes(b"!21\r\nSYNTAX invalid syntax\r\n=15\r\ntxt:Some string\r\n");
+
+    assert_eq!(
+        RespTypeRef::BulkError(b"SYNTAX invalid syntax"),
+        parser.parse().unwrap()
+    );
+    assert_eq!(
+        RespTypeRef::VerbatimString(*b"txt", b"Some string"),
+        parser.parse().unwrap()
+    );
+}
+
+#[test]
+fn parse_test_resp3_aggregates() {
+    let mut parser = Parser::new_from_bytes(
+        b"%2\r\n+first\r\n:1\r\n+second\r\n~2\r\n#t\r\n_\r\n>2\r\n+invalidate\r\n*0\r\n",
+    );
+
+    assert_eq!(
+        RespTypeRef::Map(vec![
+            (RespTypeRef::SimpleString(b"first"), RespTypeRef::Integer(1)),
+            (
+                RespTypeRef::SimpleString(b"second"),
+                RespTypeRef::Set(vec![RespTypeRef::Boolean(true), RespTypeRef::Null])
+            ),
+        ]),
+        parser.parse().unwrap()
+    );
+    assert_eq!(
+        RespTypeRef::Push(vec![
+            RespTypeRef::SimpleString(b"invalidate"),
+            RespTypeRef::Array(vec![])
+        ]),
+        parser.parse().unwrap()
+    );
+}
+
+#[test]
+fn parse_test_streamed_string() {
+    let mut parser = Parser::new_from_bytes(b"$?\r\n;4\r\nHell\r\n;5\r\no\r\nwo\r\n;0\r\n:1\r\n");
+
+    assert_eq!(
+        RespTypeRef::StreamedString(vec![b"Hell", b"o\r\nwo"]),
+        parser.parse().unwrap()
+    );
+    assert_eq!(RespTypeRef::Integer(1), parser.parse().unwrap());
+}
+
+#[test]
+fn parse_test_streamed_aggregates() {
+    let mut parser = Parser::new_from_bytes(
+        b"*?\r\n:1\r\n~?\r\n+a\r\n.\r\n.\r\n%?\r\n+key\r\n$?\r\n;1\r\nv\r\n;0\r\n.\r\n",
+    );
+
+    assert_eq!(
+        RespTypeRef::Array(vec![
+            RespTypeRef::Integer(1),
+            RespTypeRef::Set(vec![RespTypeRef::SimpleString(b"a")])
+        ]),
+        parser.parse().unwrap()
+    );
+    assert_eq!(
+        RespTypeRef::Map(vec![(
+            RespTypeRef::SimpleString(b"key"),
+            RespTypeRef::StreamedString(vec![b"v"])
+        )]),
+        parser.parse().unwrap()
+    );
+}
+
+#[test]
+fn parse_test_unexpected_stream_end() {
+    let mut parser = Parser::new_from_bytes(b"*2\r\n:1\r\n.\r\n");
+    let error = parser.parse().unwrap_err();
+
+    assert_eq!(RespErrorType::InvalidStart, error.error_type);
+    assert_eq!(b".", error.token.unwrap().data);
+}
+
+#[test]
+fn parse_spanned_bulk_string() {
+    let data = b"$5\r\nhello\r\n";
+    let spanned = Parser::new_from_bytes(data).parse_spanned().unwrap();
+
+    assert_eq!(spanned.frame, 0..11);
+    assert_eq!(&data[spanned.header.clone()], b"$5\r\n");
+    assert_eq!(&data[spanned.payload.clone()], b"hello");
+    assert_eq!(
+        spanned.node,
+        SpannedNode::Value(RespTypeRef::BulkString(b"hello"))
+    );
+}
+
+#[test]
+fn parse_spanned_array() {
+    let data = b"*3\r\n+OK\r\n$-1\r\n%1\r\n:1\r\n#t\r\n";
+    let spanned = Parser::new_from_bytes(data).parse_spanned().unwrap();
+
+    assert_eq!(spanned.frame, 0..data.len());
+    assert_eq!(&data[spanned.header.clone()], b"*3\r\n");
+    assert_eq!(&data[spanned.payload.clone()], &data[4..]);
+
+    let items = match &spanned.node {
+        SpannedNode::Array(items) => items,
+        _ => panic!("expected an array"),
+    };
+    assert_eq!(&data[items[0].frame.clone()], b"+OK\r\n");
+    assert_eq!(&data[items[0].header.clone()], b"+");
+    assert_eq!(&data[items[0].payload.clone()], b"OK");
+    assert_eq!(&data[items[1].header.clone()], b"$-1\r\n");
+    assert!(items[1].payload.is_empty());
+
+    let pairs = match &items[2].node {
+        SpannedNode::Map(pairs) => pairs,
+        _ => panic!("expected a map"),
+    };
+    assert_eq!(&data[pairs[0].0.payload.clone()], b"1");
+    assert_eq!(&data[pairs[0].1.frame.clone()], b"#t\r\n");
+
+    assert_eq!(
+        spanned.into_resp_type_ref(),
+        Parser::new_from_bytes(data).parse().unwrap()
+    );
+}
+
+#[test]
+fn parse_spanned_streamed() {
+    let data = b"*?\r\n$?\r\n;2\r\nab\r\n;0\r\n.\r\n";
+    let spanned = Parser::new_from_bytes(data).parse_spanned().unwrap();
+
+    assert_eq!(spanned.frame, 0..data.len());
+    assert_eq!(&data[spanned.payload.clone()], b"$?\r\n;2\r\nab\r\n;0\r\n");
+
+    let items = match &spanned.node {
+        SpannedNode::Array(items) => items,
+        _ => panic!("expected an array"),
+    };
+    assert_eq!(&data[items[0].header.clone()], b"$?\r\n");
+    assert_eq!(&data[items[0].payload.clone()], b";2\r\nab\r\n");
+}
+
+#[test]
+fn parse_conformance_table() {
+    use RespErrorType::*;
+
+    type Expected = Result<(), RespErrorType>;
+
+    // input, result in strict mode, result in lenient mode
+    let table: &[(&[u8], Expected, Expected)] = &[
+        (b"+OK\r\n", Ok(()), Ok(())),
+        (b"+\r\n", Ok(()), Ok(())),
+        (b"+O\rK\r\n", Err(InvalidLineCharacter), Ok(())),
+        (b"+O\nK\r\n", Err(InvalidLineCharacter), Ok(())),
+        (b"+OK\r\n+", Err(TrailingData), Ok(())),
+        (b"+OK", Err(InvalidData), Err(InvalidData)),
+        (b"-ERR unknown\r\n", Ok(()), Ok(())),
+        (b"-ERR\nunknown\r\n", Err(InvalidLineCharacter), Ok(())),
+        (b":1\r\n", Ok(()), Ok(())),
+        (b":-1\r\n", Ok(()), Ok(())),
+        (b":0\r\n", Ok(()), Ok(())),
+        (b":+1\r\n", Err(UnexpectedSign), Ok(())),
+        (b":-0\r\n", Err(UnexpectedSign), Ok(())),
+        (b":01\r\n", Err(LeadingZero), Ok(())),
+        (b": 1 \r\n", Err(InvalidInteger), Ok(())),
+        (b":1a\r\n", Err(InvalidInteger), Err(InvalidInteger)),
+        (b":\r\n", Err(InvalidInteger), Err(InvalidInteger)),
+        (b":1\r\n:2\r\n", Err(TrailingData), Ok(())),
+        (b"$9\r\n123456789\r\n", Ok(()), Ok(())),
+        (b"$0\r\n\r\n", Ok(()), Ok(())),
+        (b"$-1\r\n", Ok(()), Ok(())),
+        (b"$01\r\na\r\n", Err(LeadingZero), Ok(())),
+        (b"$-2\r\n", Err(InvalidSize), Err(InvalidSize)),
+        (b"$3\r\nab\r\n", Err(NewLineMissing), Err(NewLineMissing)),
+        (b"$2\r\nabc\r\n", Err(NewLineMissing), Err(NewLineMissing)),
+        (
+            b"*9\r\n:1\r\n:2\r\n:3\r\n:4\r\n:5\r\n:6\r\n:7\r\n:8\r\n:9\r\n",
+            Ok(()),
+            Ok(()),
+        ),
+        (b"*-1\r\n", Ok(()), Ok(())),
+        (b"*00\r\n", Err(LeadingZero), Ok(())),
+        (b"*1\r\n", Err(InvalidStart), Err(InvalidStart)),
+        (b"_\r\n", Ok(()), Ok(())),
+        (b"_x\r\n", Err(NewLineMissing), Err(NewLineMissing)),
+        (b"#t\r\n", Ok(()), Ok(())),
+        (b"#x\r\n", Err(InvalidData), Err(InvalidData)),
+        (b",1.5\r\n", Ok(()), Ok(())),
+        (b",-1.5e-10\r\n", Ok(()), Ok(())),
+        (b",inf\r\n", Ok(()), Ok(())),
+        (b",nan\r\n", Ok(()), Ok(())),
+        (b",infinity\r\n", Err(InvalidData), Ok(())),
+        (b",1.\r\n", Err(InvalidData), Ok(())),
+        (b", 1.5\r\n", Err(InvalidData), Ok(())),
+        (b",abc\r\n", Err(InvalidData), Err(InvalidData)),
+        (b"(123456789012345678901234567890\r\n", Ok(()), Ok(())),
+        (b"(12a\r\n", Err(InvalidInteger), Err(InvalidInteger)),
+        (b"!3\r\nERR\r\n", Ok(()), Ok(())),
+        (b"!-1\r\n", Err(InvalidSize), Err(InvalidSize)),
+        (b"=7\r\ntxt:abc\r\n", Ok(()), Ok(())),
+        (b"=3\r\nabc\r\n", Err(InvalidData), Err(InvalidData)),
+        (b"%1\r\n:1\r\n:2\r\n", Ok(()), Ok(())),
+        (b"%01\r\n:1\r\n:2\r\n", Err(LeadingZero), Ok(())),
+        (b"%-1\r\n", Err(InvalidSize), Err(InvalidSize)),
+        (b"~1\r\n:1\r\n", Ok(()), Ok(())),
+        (b"~-1\r\n", Err(InvalidSize), Err(InvalidSize)),
+        (b">1\r\n:1\r\n", Ok(()), Ok(())),
+        (b">?\r\n", Err(InvalidSize), Err(InvalidSize)),
+        (b"$?\r\n;1\r\na\r\n;0\r\n", Ok(()), Ok(())),
+        (b"$?\r\n;01\r\na\r\n;0\r\n", Err(LeadingZero), Ok(())),
+        (b".\r\n", Err(InvalidStart), Err(InvalidStart)),
+        (b"?\r\n", Err(InvalidStart), Err(InvalidStart)),
+        (b"", Err(InvalidStart), Err(InvalidStart)),
+    ];
+
+    for (input, strict, lenient) in table {
+        for (mode, expected) in [(ParseMode::Strict, strict), (ParseMode::Lenient, lenient)] {
+            let options = ParserOptions {
+                mode,
+                ..Default::default()
+            };
+            let result = Parser::new_with_options(Lexer::new(input), options)
+                .parse_complete()
+                .map(|_| ())
+                .map_err(|error| error.error_type);
+
+            assert_eq!(
+                &result,
+                expected,
+                "{:?} in {:?} mode",
+                String::from_utf8_lossy(input),
+                mode
+            );
+        }
+    }
+}
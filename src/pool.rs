@@ -0,0 +1,145 @@
+use alloc::vec::Vec;
+
+use crate::tape::{Cursor, NodeKind};
+use crate::RespType;
+
+// larger buffers are dropped instead of pooled, so one huge reply is not kept alive forever
+const MAX_POOLED_CAPACITY: usize = 64 * 1024;
+
+/// Keeps the vectors of frames that are handed back with `recycle`, so building new frames
+/// with `build` does not need to allocate once the pool is warmed up.
+#[derive(Debug)]
+pub struct BufferPool {
+    bytes: Vec<Vec<u8>>,
+    items: Vec<Vec<RespType>>,
+    pairs: Vec<Vec<(RespType, RespType)>>,
+    limit: usize,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        BufferPool::new(1024)
+    }
+}
+
+impl BufferPool {
+    /// `limit` is the maximum number of buffers of each kind that are kept.
+    pub fn new(limit: usize) -> BufferPool {
+        BufferPool {
+            bytes: Vec::new(),
+            items: Vec::new(),
+            pairs: Vec::new(),
+            limit,
+        }
+    }
+
+    pub fn recycle(&mut self, item: RespType) {
+        use RespType::*;
+
+        match item {
+            SimpleString(data)
+            | Error(data)
+            | BulkString(data)
+            | BigNumber(data)
+            | BulkError(data)
+            | VerbatimString(_, data) => put(&mut self.bytes, data, self.limit),
+            Array(mut data) | Set(mut data) | Push(mut data) => {
+                for item in data.drain(..) {
+                    self.recycle(item);
+                }
+                put(&mut self.items, data, self.limit);
+            }
+            Map(mut data) => {
+                for (key, value) in data.drain(..) {
+                    self.recycle(key);
+                    self.recycle(value);
+                }
+                put(&mut self.pairs, data, self.limit);
+            }
+            Integer(_) | NullString | NullArray | Null | Boolean(_) | Double(_) => (),
+        }
+    }
+
+    /// Builds an owned frame for a tape node out of pooled buffers.
+    pub fn build(&mut self, cursor: Cursor<'_, '_>) -> RespType {
+        match cursor.kind() {
+            NodeKind::SimpleString => RespType::SimpleString(self.take_bytes(cursor.payload())),
+            NodeKind::Error => RespType::Error(self.take_bytes(cursor.payload())),
+            NodeKind::Integer(x) => RespType::Integer(x),
+            NodeKind::BulkString | NodeKind::Chunk => {
+                RespType::BulkString(self.take_bytes(cursor.payload()))
+            }
+            NodeKind::NullString => RespType::NullString,
+            NodeKind::Array => RespType::Array(self.take_items(cursor)),
+            NodeKind::NullArray => RespType::NullArray,
+            NodeKind::Null => RespType::Null,
+            NodeKind::Boolean(x) => RespType::Boolean(x),
+            NodeKind::Double(x) => RespType::Double(x),
+            NodeKind::BigNumber => RespType::BigNumber(self.take_bytes(cursor.payload())),
+            NodeKind::BulkError => RespType::BulkError(self.take_bytes(cursor.payload())),
+            NodeKind::VerbatimString(format) => {
+                RespType::VerbatimString(format, self.take_bytes(cursor.payload()))
+            }
+            NodeKind::Map => {
+                let mut pairs = self.pairs.pop().unwrap_or_default();
+                let mut children = cursor.children();
+                while let (Some(key), Some(value)) = (children.next(), children.next()) {
+                    pairs.push((self.build(key), self.build(value)));
+                }
+                RespType::Map(pairs)
+            }
+            NodeKind::Set => RespType::Set(self.take_items(cursor)),
+            NodeKind::Push => RespType::Push(self.take_items(cursor)),
+            NodeKind::StreamedString => {
+                let mut data = self.bytes.pop().unwrap_or_default();
+                for chunk in cursor.children() {
+                    data.extend_from_slice(chunk.payload());
+                }
+                RespType::BulkString(data)
+            }
+        }
+    }
+
+    fn take_bytes(&mut self, payload: &[u8]) -> Vec<u8> {
+        let mut data = self.bytes.pop().unwrap_or_default();
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn take_items(&mut self, cursor: Cursor<'_, '_>) -> Vec<RespType> {
+        let mut items = self.items.pop().unwrap_or_default();
+        for child in cursor.children() {
+            items.push(self.build(child));
+        }
+        items
+    }
+}
+
+fn put<T>(pool: &mut Vec<Vec<T>>, mut buffer: Vec<T>, limit: usize) {
+    if pool.len() < limit && buffer.capacity() <= MAX_POOLED_CAPACITY {
+        buffer.clear();
+        pool.push(buffer);
+    }
+}
+
+#[test]
+fn pool_reuses_buffers() {
+    let data = b"*2\r\n$3\r\nfoo\r\n%1\r\n+a\r\n:1\r\n";
+    let tape = crate::Tape::parse(data).unwrap();
+    let mut pool = BufferPool::default();
+
+    let item = pool.build(tape.root());
+    assert_eq!(item, crate::bytes_to_resp_type(data).unwrap());
+
+    pool.recycle(item);
+    assert_eq!(
+        (pool.bytes.len(), pool.items.len(), pool.pairs.len()),
+        (2, 1, 1)
+    );
+
+    pool.build(tape.root());
+    assert_eq!(
+        (pool.bytes.len(), pool.items.len(), pool.pairs.len()),
+        (0, 0, 0)
+    );
+}
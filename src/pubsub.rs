@@ -0,0 +1,330 @@
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+use crate::{Resp, RespType, RespTypeRef};
+
+/// A message or subscription change received on a subscribed connection, from a RESP2 array
+/// or a RESP3 push frame. Converting a `RespTypeRef` borrows the fields, converting a
+/// `RespType` moves them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PubSubEvent<'a> {
+    Message {
+        channel: Cow<'a, [u8]>,
+        payload: Cow<'a, [u8]>,
+    },
+    PatternMessage {
+        pattern: Cow<'a, [u8]>,
+        channel: Cow<'a, [u8]>,
+        payload: Cow<'a, [u8]>,
+    },
+    ShardMessage {
+        channel: Cow<'a, [u8]>,
+        payload: Cow<'a, [u8]>,
+    },
+    /// `count` is the number of subscriptions the connection has afterwards.
+    Subscribe {
+        channel: Cow<'a, [u8]>,
+        count: i64,
+    },
+    /// `channel` is `None` when there was nothing to unsubscribe from.
+    Unsubscribe {
+        channel: Option<Cow<'a, [u8]>>,
+        count: i64,
+    },
+    PatternSubscribe {
+        pattern: Cow<'a, [u8]>,
+        count: i64,
+    },
+    PatternUnsubscribe {
+        pattern: Option<Cow<'a, [u8]>>,
+        count: i64,
+    },
+    ShardSubscribe {
+        channel: Cow<'a, [u8]>,
+        count: i64,
+    },
+    ShardUnsubscribe {
+        channel: Option<Cow<'a, [u8]>>,
+        count: i64,
+    },
+    /// Reply to `PING` while subscribed on a RESP2 connection.
+    Pong(Cow<'a, [u8]>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PubSubError {
+    /// The frame is not an array or push, e.g. a normal command reply.
+    NotPubSub,
+    UnknownKind(Vec<u8>),
+    /// The kind is known but the number or types of the fields are wrong.
+    Malformed,
+}
+
+impl Display for PubSubError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        match self {
+            PubSubError::NotPubSub => write!(f, "frame is not a pub/sub event"),
+            PubSubError::UnknownKind(kind) => write!(
+                f,
+                "unknown pub/sub event {}",
+                alloc::string::String::from_utf8_lossy(kind)
+            ),
+            PubSubError::Malformed => write!(f, "malformed pub/sub event"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PubSubError {}
+
+impl PubSubEvent<'_> {
+    pub fn into_owned(self) -> PubSubEvent<'static> {
+        fn owned(data: Cow<'_, [u8]>) -> Cow<'static, [u8]> {
+            Cow::Owned(data.into_owned())
+        }
+
+        match self {
+            PubSubEvent::Message { channel, payload } => PubSubEvent::Message {
+                channel: owned(channel),
+                payload: owned(payload),
+            },
+            PubSubEvent::PatternMessage {
+                pattern,
+                channel,
+                payload,
+            } => PubSubEvent::PatternMessage {
+                pattern: owned(pattern),
+                channel: owned(channel),
+                payload: owned(payload),
+            },
+            PubSubEvent::ShardMessage { channel, payload } => PubSubEvent::ShardMessage {
+                channel: owned(channel),
+                payload: owned(payload),
+            },
+            PubSubEvent::Subscribe { channel, count } => PubSubEvent::Subscribe {
+                channel: owned(channel),
+                count,
+            },
+            PubSubEvent::Unsubscribe { channel, count } => PubSubEvent::Unsubscribe {
+                channel: channel.map(owned),
+                count,
+            },
+            PubSubEvent::PatternSubscribe { pattern, count } => PubSubEvent::PatternSubscribe {
+                pattern: owned(pattern),
+                count,
+            },
+            PubSubEvent::PatternUnsubscribe { pattern, count } => PubSubEvent::PatternUnsubscribe {
+                pattern: pattern.map(owned),
+                count,
+            },
+            PubSubEvent::ShardSubscribe { channel, count } => PubSubEvent::ShardSubscribe {
+                channel: owned(channel),
+                count,
+            },
+            PubSubEvent::ShardUnsubscribe { channel, count } => PubSubEvent::ShardUnsubscribe {
+                channel: channel.map(owned),
+                count,
+            },
+            PubSubEvent::Pong(payload) => PubSubEvent::Pong(owned(payload)),
+        }
+    }
+}
+
+fn bytes(item: Resp<'_>) -> Result<Cow<'_, [u8]>, PubSubError> {
+    item.into_bytes().ok_or(PubSubError::Malformed)
+}
+
+fn optional_bytes(item: Resp<'_>) -> Result<Option<Cow<'_, [u8]>>, PubSubError> {
+    if item.is_null() {
+        return Ok(None);
+    }
+    bytes(item).map(Some)
+}
+
+fn count(item: Resp<'_>) -> Result<i64, PubSubError> {
+    match item {
+        Resp::Integer(count) => Ok(count),
+        _ => Err(PubSubError::Malformed),
+    }
+}
+
+impl<'a> TryFrom<Resp<'a>> for PubSubEvent<'a> {
+    type Error = PubSubError;
+
+    fn try_from(frame: Resp<'a>) -> Result<Self, Self::Error> {
+        let items = match frame {
+            Resp::Array(items) | Resp::Push(items) => items,
+            _ => return Err(PubSubError::NotPubSub),
+        };
+
+        let mut items = items.into_iter();
+        let kind = items
+            .next()
+            .and_then(|kind| kind.into_bytes())
+            .ok_or(PubSubError::NotPubSub)?;
+        let fields: Vec<Resp<'a>> = items.collect();
+
+        // a pong can be sent without payload
+        if kind.as_ref() == b"pong" && fields.len() <= 1 {
+            let payload = match fields.into_iter().next() {
+                Some(payload) => bytes(payload)?,
+                None => Cow::Borrowed(&b""[..]),
+            };
+            return Ok(PubSubEvent::Pong(payload));
+        }
+
+        let event = match (kind.as_ref(), <[Resp<'a>; 2]>::try_from(fields)) {
+            (b"message", Ok([channel, payload])) => PubSubEvent::Message {
+                channel: bytes(channel)?,
+                payload: bytes(payload)?,
+            },
+            (b"smessage", Ok([channel, payload])) => PubSubEvent::ShardMessage {
+                channel: bytes(channel)?,
+                payload: bytes(payload)?,
+            },
+            (b"subscribe", Ok([channel, number])) => PubSubEvent::Subscribe {
+                channel: bytes(channel)?,
+                count: count(number)?,
+            },
+            (b"unsubscribe", Ok([channel, number])) => PubSubEvent::Unsubscribe {
+                channel: optional_bytes(channel)?,
+                count: count(number)?,
+            },
+            (b"psubscribe", Ok([pattern, number])) => PubSubEvent::PatternSubscribe {
+                pattern: bytes(pattern)?,
+                count: count(number)?,
+            },
+            (b"punsubscribe", Ok([pattern, number])) => PubSubEvent::PatternUnsubscribe {
+                pattern: optional_bytes(pattern)?,
+                count: count(number)?,
+            },
+            (b"ssubscribe", Ok([channel, number])) => PubSubEvent::ShardSubscribe {
+                channel: bytes(channel)?,
+                count: count(number)?,
+            },
+            (b"sunsubscribe", Ok([channel, number])) => PubSubEvent::ShardUnsubscribe {
+                channel: optional_bytes(channel)?,
+                count: count(number)?,
+            },
+            (b"pmessage", Err(fields)) => match <[Resp<'a>; 3]>::try_from(fields) {
+                Ok([pattern, channel, payload]) => PubSubEvent::PatternMessage {
+                    pattern: bytes(pattern)?,
+                    channel: bytes(channel)?,
+                    payload: bytes(payload)?,
+                },
+                Err(_) => return Err(PubSubError::Malformed),
+            },
+            (
+                b"message" | b"smessage" | b"subscribe" | b"unsubscribe" | b"psubscribe"
+                | b"punsubscribe" | b"ssubscribe" | b"sunsubscribe" | b"pmessage" | b"pong",
+                _,
+            ) => return Err(PubSubError::Malformed),
+            (kind, _) => return Err(PubSubError::UnknownKind(kind.to_vec())),
+        };
+
+        Ok(event)
+    }
+}
+
+impl<'a> TryFrom<RespTypeRef<'a>> for PubSubEvent<'a> {
+    type Error = PubSubError;
+
+    fn try_from(frame: RespTypeRef<'a>) -> Result<Self, Self::Error> {
+        PubSubEvent::try_from(Resp::from(frame))
+    }
+}
+
+impl TryFrom<RespType> for PubSubEvent<'static> {
+    type Error = PubSubError;
+
+    fn try_from(frame: RespType) -> Result<Self, Self::Error> {
+        PubSubEvent::try_from(Resp::from(frame))
+    }
+}
+
+#[test]
+fn pubsub_resp2_events() {
+    let data = b"*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n";
+    let frame = crate::Parser::new_from_bytes(data).parse().unwrap();
+    let event = PubSubEvent::try_from(frame).unwrap();
+
+    assert!(matches!(
+        &event,
+        PubSubEvent::Message {
+            channel: Cow::Borrowed(b"news"),
+            payload: Cow::Borrowed(b"hello"),
+        }
+    ));
+    assert_eq!(event.clone().into_owned(), event);
+
+    let data = b"*4\r\n$8\r\npmessage\r\n$2\r\nn*\r\n$4\r\nnews\r\n$2\r\nhi\r\n";
+    assert_eq!(
+        PubSubEvent::try_from(crate::bytes_to_resp_type(data).unwrap()),
+        Ok(PubSubEvent::PatternMessage {
+            pattern: Cow::Borrowed(b"n*"),
+            channel: Cow::Borrowed(b"news"),
+            payload: Cow::Borrowed(b"hi"),
+        })
+    );
+
+    let data = b"*3\r\n$11\r\nunsubscribe\r\n$-1\r\n:0\r\n";
+    assert_eq!(
+        PubSubEvent::try_from(crate::bytes_to_resp_type(data).unwrap()),
+        Ok(PubSubEvent::Unsubscribe {
+            channel: None,
+            count: 0
+        })
+    );
+
+    let data = b"*2\r\n$4\r\npong\r\n$0\r\n\r\n";
+    assert_eq!(
+        PubSubEvent::try_from(crate::bytes_to_resp_type(data).unwrap()),
+        Ok(PubSubEvent::Pong(Cow::Borrowed(b"")))
+    );
+}
+
+#[test]
+fn pubsub_resp3_push_events() {
+    let data = b">3\r\n$10\r\nssubscribe\r\n$5\r\nshard\r\n:1\r\n";
+    assert_eq!(
+        PubSubEvent::try_from(crate::bytes_to_resp_type(data).unwrap()),
+        Ok(PubSubEvent::ShardSubscribe {
+            channel: Cow::Borrowed(b"shard"),
+            count: 1
+        })
+    );
+
+    let data = b">3\r\n$8\r\nsmessage\r\n$5\r\nshard\r\n$?\r\n;2\r\nhe\r\n;3\r\nllo\r\n;0\r\n";
+    assert_eq!(
+        PubSubEvent::try_from(crate::Parser::new_from_bytes(data).parse().unwrap()),
+        Ok(PubSubEvent::ShardMessage {
+            channel: Cow::Borrowed(b"shard"),
+            payload: Cow::Borrowed(b"hello"),
+        })
+    );
+}
+
+#[test]
+fn pubsub_malformed_events() {
+    let parse = |data: &[u8]| PubSubEvent::try_from(crate::bytes_to_resp_type(data).unwrap());
+
+    assert_eq!(parse(b"+OK\r\n"), Err(PubSubError::NotPubSub));
+    assert_eq!(parse(b"*0\r\n"), Err(PubSubError::NotPubSub));
+    assert_eq!(
+        parse(b"*2\r\n$7\r\nmessage\r\n$4\r\nnews\r\n"),
+        Err(PubSubError::Malformed)
+    );
+    assert_eq!(
+        parse(b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n$1\r\n1\r\n"),
+        Err(PubSubError::Malformed)
+    );
+    assert_eq!(
+        parse(b"*3\r\n$7\r\nmessage\r\n*0\r\n$2\r\nhi\r\n"),
+        Err(PubSubError::Malformed)
+    );
+    assert_eq!(
+        parse(b"*2\r\n$4\r\nnope\r\n:1\r\n"),
+        Err(PubSubError::UnknownKind(b"nope".to_vec()))
+    );
+}
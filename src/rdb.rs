@@ -0,0 +1,1743 @@
+use std::fmt::Display;
+use std::io::{ErrorKind, Read};
+
+use crate::Command;
+
+// values are written with at most this many elements per command, like an AOF rewrite does
+const ITEMS_PER_COMMAND: usize = 64;
+
+type Pairs = Vec<(Vec<u8>, Vec<u8>)>;
+
+#[derive(Debug)]
+pub enum RdbError {
+    Io(std::io::Error),
+    /// The input does not start with `REDIS` and a four digit version.
+    InvalidHeader,
+    UnsupportedType(u8),
+    UnsupportedOpcode(u8),
+    /// An encoding inside a value is inconsistent, e.g. a ziplist that ends too early.
+    Invalid(&'static str),
+    ChecksumMismatch {
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl Display for RdbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            RdbError::Io(error) => write!(f, "{}", error),
+            RdbError::InvalidHeader => write!(f, "not an RDB file"),
+            RdbError::UnsupportedType(value_type) => {
+                write!(f, "unsupported value type {}", value_type)
+            }
+            RdbError::UnsupportedOpcode(opcode) => write!(f, "unsupported opcode {:#x}", opcode),
+            RdbError::Invalid(what) => write!(f, "invalid {}", what),
+            RdbError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "checksum is {:#018x} but the data has {:#018x}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RdbError {}
+
+impl From<std::io::Error> for RdbError {
+    fn from(error: std::io::Error) -> Self {
+        RdbError::Io(error)
+    }
+}
+
+const fn crc64_table() -> [u64; 256] {
+    // reflected form of the Jones polynomial 0xad93d23594c935a9 that Redis uses
+    const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+static CRC64_TABLE: [u64; 256] = crc64_table();
+
+/// Continues the CRC-64 Redis uses for RDB files and `DUMP` payloads, start with 0.
+pub fn crc64(crc: u64, data: &[u8]) -> u64 {
+    data.iter().fold(crc, |crc, &byte| {
+        CRC64_TABLE[((crc ^ byte as u64) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl Display for StreamId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+impl StreamId {
+    fn from_bytes(data: &[u8]) -> Option<StreamId> {
+        let data = <[u8; 16]>::try_from(data).ok()?;
+        Some(StreamId {
+            ms: u64::from_be_bytes(data[..8].try_into().unwrap()),
+            seq: u64::from_be_bytes(data[8..].try_into().unwrap()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamEntry {
+    pub id: StreamId,
+    pub fields: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamPending {
+    pub id: StreamId,
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamConsumer {
+    pub name: Vec<u8>,
+    pub seen_time: u64,
+    pub active_time: Option<u64>,
+    pub pending: Vec<StreamId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamGroup {
+    pub name: Vec<u8>,
+    pub last_id: StreamId,
+    pub entries_read: Option<u64>,
+    pub pending: Vec<StreamPending>,
+    pub consumers: Vec<StreamConsumer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Stream {
+    pub entries: Vec<StreamEntry>,
+    pub length: u64,
+    pub last_id: StreamId,
+    /// Only stored since RDB 10 (Redis 7).
+    pub first_id: Option<StreamId>,
+    pub max_deleted_id: Option<StreamId>,
+    pub entries_added: Option<u64>,
+    pub groups: Vec<StreamGroup>,
+}
+
+/// A value as Redis stores it, whatever encoding it had in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RdbValue {
+    String(Vec<u8>),
+    List(Vec<Vec<u8>>),
+    Set(Vec<Vec<u8>>),
+    SortedSet(Vec<(Vec<u8>, f64)>),
+    Hash(Vec<(Vec<u8>, Vec<u8>)>),
+    Stream(Box<Stream>),
+    /// Module values are skipped, only the module id is kept.
+    Module(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RdbRecord {
+    pub database: u64,
+    pub key: Vec<u8>,
+    pub value: RdbValue,
+    /// Unix time in milliseconds.
+    pub expire_at: Option<u64>,
+}
+
+impl RdbRecord {
+    /// Commands that recreate the key in the current database, followed by `PEXPIREAT` if it
+    /// expires. Module values give no commands.
+    pub fn to_commands(&self) -> Vec<Command> {
+        let key = self.key.as_slice();
+        let mut commands = Vec::new();
+
+        let batched = |commands: &mut Vec<Command>, name: &str, args: &[&[u8]], per_item: usize| {
+            for chunk in args.chunks(ITEMS_PER_COMMAND * per_item) {
+                let command = chunk
+                    .iter()
+                    .fold(Command::new(name).arg(key), |command, arg| command.arg(arg));
+                commands.push(command);
+            }
+        };
+
+        match &self.value {
+            RdbValue::String(value) => commands.push(Command::new("SET").arg(key).arg(value)),
+            RdbValue::List(items) => {
+                let args: Vec<&[u8]> = items.iter().map(|x| x.as_slice()).collect();
+                batched(&mut commands, "RPUSH", &args, 1);
+            }
+            RdbValue::Set(members) => {
+                let args: Vec<&[u8]> = members.iter().map(|x| x.as_slice()).collect();
+                batched(&mut commands, "SADD", &args, 1);
+            }
+            RdbValue::SortedSet(members) => {
+                let scores: Vec<String> =
+                    members.iter().map(|(_, score)| score.to_string()).collect();
+                let args: Vec<&[u8]> = members
+                    .iter()
+                    .zip(&scores)
+                    .flat_map(|((member, _), score)| [score.as_bytes(), member.as_slice()])
+                    .collect();
+                batched(&mut commands, "ZADD", &args, 2);
+            }
+            RdbValue::Hash(fields) => {
+                let args: Vec<&[u8]> = fields
+                    .iter()
+                    .flat_map(|(field, value)| [field.as_slice(), value.as_slice()])
+                    .collect();
+                batched(&mut commands, "HSET", &args, 2);
+            }
+            RdbValue::Stream(stream) => stream_commands(key, stream, &mut commands),
+            RdbValue::Module(_) => return commands,
+        }
+
+        if let Some(expire_at) = self.expire_at {
+            commands.push(
+                Command::new("PEXPIREAT")
+                    .arg(key)
+                    .arg(expire_at.to_string()),
+            );
+        }
+
+        commands
+    }
+}
+
+fn stream_commands(key: &[u8], stream: &Stream, commands: &mut Vec<Command>) {
+    for entry in &stream.entries {
+        let command = Command::new("XADD").arg(key).arg(entry.id.to_string());
+        commands.push(
+            entry
+                .fields
+                .iter()
+                .fold(command, |command, (field, value)| {
+                    command.arg(field).arg(value)
+                }),
+        );
+    }
+
+    // an empty stream can only be created by adding an entry and trimming it right away
+    if stream.entries.is_empty() {
+        commands.push(
+            Command::new("XADD")
+                .arg(key)
+                .arg("MAXLEN")
+                .arg("0")
+                .arg(stream.last_id.to_string())
+                .arg("x")
+                .arg("y"),
+        );
+    }
+
+    let mut setid = Command::new("XSETID")
+        .arg(key)
+        .arg(stream.last_id.to_string());
+    if let (Some(entries_added), Some(max_deleted_id)) =
+        (stream.entries_added, stream.max_deleted_id)
+    {
+        setid = setid
+            .arg("ENTRIESADDED")
+            .arg(entries_added.to_string())
+            .arg("MAXDELETEDID")
+            .arg(max_deleted_id.to_string());
+    }
+    commands.push(setid);
+
+    for group in &stream.groups {
+        let mut create = Command::new("XGROUP")
+            .arg("CREATE")
+            .arg(key)
+            .arg(&group.name)
+            .arg(group.last_id.to_string());
+        if let Some(entries_read) = group.entries_read {
+            create = create.arg("ENTRIESREAD").arg(entries_read.to_string());
+        }
+        commands.push(create);
+
+        for consumer in &group.consumers {
+            commands.push(
+                Command::new("XGROUP")
+                    .arg("CREATECONSUMER")
+                    .arg(key)
+                    .arg(&group.name)
+                    .arg(&consumer.name),
+            );
+
+            for id in &consumer.pending {
+                let Some(pending) = group.pending.iter().find(|x| x.id == *id) else {
+                    continue;
+                };
+                commands.push(
+                    Command::new("XCLAIM")
+                        .arg(key)
+                        .arg(&group.name)
+                        .arg(&consumer.name)
+                        .arg("0")
+                        .arg(id.to_string())
+                        .arg("TIME")
+                        .arg(pending.delivery_time.to_string())
+                        .arg("RETRYCOUNT")
+                        .arg(pending.delivery_count.to_string())
+                        .arg("FORCE")
+                        .arg("JUSTID")
+                        .arg("LASTID")
+                        .arg(group.last_id.to_string()),
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RdbEvent {
+    /// Metadata like `redis-ver` or `ctime`.
+    Aux {
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    /// Code of a function library, loaded with `FUNCTION LOAD`.
+    Function(Vec<u8>),
+    Record(RdbRecord),
+}
+
+// Reads from the file and keeps the checksum of everything read so far.
+pub(crate) struct Input<R> {
+    reader: R,
+    crc: u64,
+}
+
+enum Length {
+    Len(u64),
+    // integer or compressed string
+    Encoded(u8),
+}
+
+impl<R: Read> Input<R> {
+    pub(crate) fn new(reader: R) -> Input<R> {
+        Input { reader, crc: 0 }
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+
+    pub(crate) fn crc(&self) -> u64 {
+        self.crc
+    }
+
+    pub(crate) fn bytes(&mut self, len: u64) -> Result<Vec<u8>, RdbError> {
+        // `take` so a corrupt length does not allocate everything up front
+        let mut data = Vec::new();
+        (&mut self.reader).take(len).read_to_end(&mut data)?;
+        if (data.len() as u64) < len {
+            return Err(RdbError::Io(ErrorKind::UnexpectedEof.into()));
+        }
+
+        self.crc = crc64(self.crc, &data);
+        Ok(data)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], RdbError> {
+        let mut data = [0; N];
+        self.reader.read_exact(&mut data)?;
+        self.crc = crc64(self.crc, &data);
+        Ok(data)
+    }
+
+    // trailing checksums are not part of the checksum
+    pub(crate) fn unchecked_u64(&mut self) -> Result<u64, RdbError> {
+        let mut data = [0; 8];
+        self.reader.read_exact(&mut data)?;
+        Ok(u64::from_le_bytes(data))
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8, RdbError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn millis(&mut self) -> Result<u64, RdbError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn length(&mut self) -> Result<Length, RdbError> {
+        let first = self.byte()?;
+
+        Ok(match first >> 6 {
+            0 => Length::Len((first & 0x3f) as u64),
+            1 => Length::Len((((first & 0x3f) as u64) << 8) | self.byte()? as u64),
+            2 => match first {
+                0x80 => Length::Len(u32::from_be_bytes(self.array()?) as u64),
+                0x81 => Length::Len(u64::from_be_bytes(self.array()?)),
+                _ => return Err(RdbError::Invalid("length")),
+            },
+            _ => Length::Encoded(first & 0x3f),
+        })
+    }
+
+    fn len(&mut self) -> Result<u64, RdbError> {
+        match self.length()? {
+            Length::Len(len) => Ok(len),
+            Length::Encoded(_) => Err(RdbError::Invalid("length")),
+        }
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>, RdbError> {
+        match self.length()? {
+            Length::Len(len) => self.bytes(len),
+            Length::Encoded(0) => Ok((self.array::<1>()?[0] as i8).to_string().into_bytes()),
+            Length::Encoded(1) => Ok(i16::from_le_bytes(self.array()?).to_string().into_bytes()),
+            Length::Encoded(2) => Ok(i32::from_le_bytes(self.array()?).to_string().into_bytes()),
+            Length::Encoded(3) => {
+                let compressed_len = self.len()?;
+                let len = self.len()?;
+                let compressed = self.bytes(compressed_len)?;
+                lzf_decompress(&compressed, len)
+            }
+            Length::Encoded(_) => Err(RdbError::Invalid("string encoding")),
+        }
+    }
+
+    // scores of the old zset type are written as text
+    fn text_double(&mut self) -> Result<f64, RdbError> {
+        match self.byte()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            len => parse_double(&self.bytes(len as u64)?),
+        }
+    }
+
+    fn strings(&mut self) -> Result<Vec<Vec<u8>>, RdbError> {
+        let len = self.len()?;
+        (0..len).map(|_| self.string()).collect()
+    }
+
+    fn stream_id(&mut self) -> Result<StreamId, RdbError> {
+        Ok(StreamId {
+            ms: self.len()?,
+            seq: self.len()?,
+        })
+    }
+
+    fn raw_stream_id(&mut self) -> Result<StreamId, RdbError> {
+        Ok(StreamId::from_bytes(&self.array::<16>()?).unwrap())
+    }
+
+    // module values are a sequence of typed fields ending with an EOF opcode
+    fn skip_module_value(&mut self) -> Result<(), RdbError> {
+        loop {
+            match self.len()? {
+                0 => return Ok(()),
+                1 | 2 => {
+                    self.len()?;
+                }
+                3 => {
+                    self.array::<4>()?;
+                }
+                4 => {
+                    self.array::<8>()?;
+                }
+                5 => {
+                    self.string()?;
+                }
+                _ => return Err(RdbError::Invalid("module value")),
+            }
+        }
+    }
+
+    pub(crate) fn object(&mut self, value_type: u8) -> Result<RdbValue, RdbError> {
+        let pairs = |items: Vec<Element>| -> Result<Pairs, RdbError> {
+            if !items.len().is_multiple_of(2) {
+                return Err(RdbError::Invalid("pairs"));
+            }
+            let mut items = items.into_iter().map(Element::into_bytes);
+            Ok(std::iter::from_fn(|| Some((items.next()?, items.next()?))).collect())
+        };
+        let scored = |items: Vec<Element>| -> Result<Vec<(Vec<u8>, f64)>, RdbError> {
+            pairs(items)?
+                .into_iter()
+                .map(|(member, score)| Ok((member, parse_double(&score)?)))
+                .collect()
+        };
+        let bytes = |items: Vec<Element>| items.into_iter().map(Element::into_bytes).collect();
+
+        Ok(match value_type {
+            0 => RdbValue::String(self.string()?),
+            1 => RdbValue::List(self.strings()?),
+            2 => RdbValue::Set(self.strings()?),
+            3 => {
+                let len = self.len()?;
+                let members = (0..len)
+                    .map(|_| Ok((self.string()?, self.text_double()?)))
+                    .collect::<Result<_, RdbError>>()?;
+                RdbValue::SortedSet(members)
+            }
+            4 => {
+                let len = self.len()?;
+                let fields = (0..len)
+                    .map(|_| Ok((self.string()?, self.string()?)))
+                    .collect::<Result<_, RdbError>>()?;
+                RdbValue::Hash(fields)
+            }
+            5 => {
+                let len = self.len()?;
+                let members = (0..len)
+                    .map(|_| Ok((self.string()?, f64::from_le_bytes(self.array()?))))
+                    .collect::<Result<_, RdbError>>()?;
+                RdbValue::SortedSet(members)
+            }
+            7 => {
+                let id = self.len()?;
+                self.skip_module_value()?;
+                RdbValue::Module(id)
+            }
+            9 => RdbValue::Hash(zipmap(&self.string()?)?),
+            10 => RdbValue::List(bytes(ziplist(&self.string()?)?)),
+            11 => RdbValue::Set(intset(&self.string()?)?),
+            12 => RdbValue::SortedSet(scored(ziplist(&self.string()?)?)?),
+            13 => RdbValue::Hash(pairs(ziplist(&self.string()?)?)?),
+            14 => {
+                let mut items = Vec::new();
+                for _ in 0..self.len()? {
+                    items.extend(bytes(ziplist(&self.string()?)?));
+                }
+                RdbValue::List(items)
+            }
+            15 | 19 | 21 => RdbValue::Stream(Box::new(self.stream(value_type)?)),
+            16 => RdbValue::Hash(pairs(listpack(&self.string()?)?)?),
+            17 => RdbValue::SortedSet(scored(listpack(&self.string()?)?)?),
+            18 => {
+                let mut items = Vec::new();
+                for _ in 0..self.len()? {
+                    match self.len()? {
+                        // a single large element stored as is
+                        1 => items.push(self.string()?),
+                        2 => items.extend(bytes(listpack(&self.string()?)?)),
+                        _ => return Err(RdbError::Invalid("quicklist container")),
+                    }
+                }
+                RdbValue::List(items)
+            }
+            20 => RdbValue::Set(bytes(listpack(&self.string()?)?)),
+            _ => return Err(RdbError::UnsupportedType(value_type)),
+        })
+    }
+
+    fn stream(&mut self, value_type: u8) -> Result<Stream, RdbError> {
+        let mut stream = Stream::default();
+
+        for _ in 0..self.len()? {
+            let master_id =
+                StreamId::from_bytes(&self.string()?).ok_or(RdbError::Invalid("stream id"))?;
+            stream_entries(master_id, listpack(&self.string()?)?, &mut stream.entries)?;
+        }
+
+        stream.length = self.len()?;
+        stream.last_id = self.stream_id()?;
+        if value_type >= 19 {
+            stream.first_id = Some(self.stream_id()?);
+            stream.max_deleted_id = Some(self.stream_id()?);
+            stream.entries_added = Some(self.len()?);
+        }
+
+        for _ in 0..self.len()? {
+            let name = self.string()?;
+            let last_id = self.stream_id()?;
+            // -1 when the number of entries read is unknown
+            let entries_read = match value_type >= 19 {
+                true => Some(self.len()?).filter(|&x| x != u64::MAX),
+                false => None,
+            };
+
+            let pending = (0..self.len()?)
+                .map(|_| {
+                    Ok(StreamPending {
+                        id: self.raw_stream_id()?,
+                        delivery_time: self.millis()?,
+                        delivery_count: self.len()?,
+                    })
+                })
+                .collect::<Result<_, RdbError>>()?;
+
+            let consumers = (0..self.len()?)
+                .map(|_| {
+                    Ok(StreamConsumer {
+                        name: self.string()?,
+                        seen_time: self.millis()?,
+                        active_time: match value_type >= 21 {
+                            true => Some(self.millis()?),
+                            false => None,
+                        },
+                        pending: (0..self.len()?)
+                            .map(|_| self.raw_stream_id())
+                            .collect::<Result<_, _>>()?,
+                    })
+                })
+                .collect::<Result<_, RdbError>>()?;
+
+            stream.groups.push(StreamGroup {
+                name,
+                last_id,
+                entries_read,
+                pending,
+                consumers,
+            });
+        }
+
+        Ok(stream)
+    }
+}
+
+fn parse_double(data: &[u8]) -> Result<f64, RdbError> {
+    std::str::from_utf8(data)
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .ok_or(RdbError::Invalid("double"))
+}
+
+pub(crate) fn lzf_decompress(input: &[u8], len: u64) -> Result<Vec<u8>, RdbError> {
+    let error = || RdbError::Invalid("compressed string");
+    let mut output = Vec::with_capacity(len.min(input.len() as u64 * 16) as usize);
+    let mut index = 0;
+
+    while index < input.len() {
+        let control = input[index] as usize;
+        index += 1;
+
+        if control < 32 {
+            let literal = input.get(index..index + control + 1).ok_or_else(error)?;
+            output.extend_from_slice(literal);
+            index += control + 1;
+        } else {
+            let mut run = control >> 5;
+            if run == 7 {
+                run += *input.get(index).ok_or_else(error)? as usize;
+                index += 1;
+            }
+            let distance =
+                ((control & 0x1f) << 8) + *input.get(index).ok_or_else(error)? as usize + 1;
+            index += 1;
+
+            let start = output.len().checked_sub(distance).ok_or_else(error)?;
+            // the copy may overlap the bytes it produces
+            for position in start..start + run + 2 {
+                output.push(output[position]);
+            }
+        }
+    }
+
+    if output.len() as u64 != len {
+        return Err(error());
+    }
+    Ok(output)
+}
+
+// Element of a ziplist or listpack, small integers are stored as numbers.
+#[derive(Debug, Clone, PartialEq)]
+enum Element {
+    Bytes(Vec<u8>),
+    Integer(i64),
+}
+
+impl Element {
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Element::Bytes(data) => data,
+            Element::Integer(value) => value.to_string().into_bytes(),
+        }
+    }
+
+    fn integer(&self) -> Result<i64, RdbError> {
+        match self {
+            Element::Integer(value) => Ok(*value),
+            Element::Bytes(data) => std::str::from_utf8(data)
+                .ok()
+                .and_then(|x| x.parse().ok())
+                .ok_or(RdbError::Invalid("integer")),
+        }
+    }
+}
+
+struct Blob<'a> {
+    data: &'a [u8],
+    position: usize,
+    what: &'static str,
+}
+
+impl<'a> Blob<'a> {
+    fn new(data: &'a [u8], what: &'static str) -> Blob<'a> {
+        Blob {
+            data,
+            position: 0,
+            what,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], RdbError> {
+        let data = self
+            .data
+            .get(self.position..self.position + len)
+            .ok_or(RdbError::Invalid(self.what))?;
+        self.position += len;
+        Ok(data)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], RdbError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn byte(&mut self) -> Result<u8, RdbError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn peek(&self) -> Result<u8, RdbError> {
+        self.data
+            .get(self.position)
+            .copied()
+            .ok_or(RdbError::Invalid(self.what))
+    }
+
+    fn int24(&mut self) -> Result<i64, RdbError> {
+        let [a, b, c] = self.array()?;
+        Ok((i32::from_le_bytes([0, a, b, c]) >> 8) as i64)
+    }
+}
+
+fn ziplist(data: &[u8]) -> Result<Vec<Element>, RdbError> {
+    let mut blob = Blob::new(data, "ziplist");
+    blob.take(10)?;
+    let mut elements = Vec::new();
+
+    while blob.peek()? != 0xff {
+        // length of the previous entry
+        if blob.byte()? == 0xfe {
+            blob.take(4)?;
+        }
+
+        let encoding = blob.byte()?;
+        let element = match encoding >> 6 {
+            0 => Element::Bytes(blob.take((encoding & 0x3f) as usize)?.to_vec()),
+            1 => {
+                let len = (((encoding & 0x3f) as usize) << 8) | blob.byte()? as usize;
+                Element::Bytes(blob.take(len)?.to_vec())
+            }
+            2 => {
+                let len = u32::from_be_bytes(blob.array()?) as usize;
+                Element::Bytes(blob.take(len)?.to_vec())
+            }
+            _ => Element::Integer(match encoding {
+                0xc0 => i16::from_le_bytes(blob.array()?) as i64,
+                0xd0 => i32::from_le_bytes(blob.array()?) as i64,
+                0xe0 => i64::from_le_bytes(blob.array()?),
+                0xf0 => blob.int24()?,
+                0xfe => blob.byte()? as i8 as i64,
+                0xf1..=0xfd => (encoding & 0x0f) as i64 - 1,
+                _ => return Err(RdbError::Invalid("ziplist")),
+            }),
+        };
+        elements.push(element);
+    }
+
+    Ok(elements)
+}
+
+fn listpack(data: &[u8]) -> Result<Vec<Element>, RdbError> {
+    let mut blob = Blob::new(data, "listpack");
+    blob.take(6)?;
+    let mut elements = Vec::new();
+
+    while blob.peek()? != 0xff {
+        let start = blob.position;
+        let encoding = blob.byte()?;
+
+        let element = if encoding & 0x80 == 0 {
+            Element::Integer((encoding & 0x7f) as i64)
+        } else if encoding & 0xc0 == 0x80 {
+            Element::Bytes(blob.take((encoding & 0x3f) as usize)?.to_vec())
+        } else if encoding & 0xe0 == 0xc0 {
+            let value = (((encoding & 0x1f) as i64) << 8) | blob.byte()? as i64;
+            // 13 bit two's complement
+            Element::Integer(if value >= 1 << 12 {
+                value - (1 << 13)
+            } else {
+                value
+            })
+        } else if encoding & 0xf0 == 0xe0 {
+            let len = (((encoding & 0x0f) as usize) << 8) | blob.byte()? as usize;
+            Element::Bytes(blob.take(len)?.to_vec())
+        } else {
+            match encoding {
+                0xf0 => {
+                    let len = u32::from_le_bytes(blob.array()?) as usize;
+                    Element::Bytes(blob.take(len)?.to_vec())
+                }
+                0xf1 => Element::Integer(i16::from_le_bytes(blob.array()?) as i64),
+                0xf2 => Element::Integer(blob.int24()?),
+                0xf3 => Element::Integer(i32::from_le_bytes(blob.array()?) as i64),
+                0xf4 => Element::Integer(i64::from_le_bytes(blob.array()?)),
+                _ => return Err(RdbError::Invalid("listpack")),
+            }
+        };
+
+        // every entry ends with its own length, used to walk the listpack backwards
+        blob.take(backlen_size(blob.position - start))?;
+        elements.push(element);
+    }
+
+    Ok(elements)
+}
+
+fn intset(data: &[u8]) -> Result<Vec<Vec<u8>>, RdbError> {
+    let mut blob = Blob::new(data, "intset");
+    let width = u32::from_le_bytes(blob.array()?);
+    let len = u32::from_le_bytes(blob.array()?);
+
+    (0..len)
+        .map(|_| {
+            let value = match width {
+                2 => i16::from_le_bytes(blob.array()?) as i64,
+                4 => i32::from_le_bytes(blob.array()?) as i64,
+                8 => i64::from_le_bytes(blob.array()?),
+                _ => return Err(RdbError::Invalid("intset")),
+            };
+            Ok(value.to_string().into_bytes())
+        })
+        .collect()
+}
+
+fn zipmap(data: &[u8]) -> Result<Pairs, RdbError> {
+    let mut blob = Blob::new(data, "zipmap");
+    blob.byte()?;
+
+    let len = |blob: &mut Blob| -> Result<Option<usize>, RdbError> {
+        Ok(match blob.byte()? {
+            255 => None,
+            254 => Some(u32::from_le_bytes(blob.array()?) as usize),
+            len => Some(len as usize),
+        })
+    };
+
+    let mut fields = Vec::new();
+    while let Some(key_len) = len(&mut blob)? {
+        let key = blob.take(key_len)?.to_vec();
+        let value_len = len(&mut blob)?.ok_or(RdbError::Invalid("zipmap"))?;
+        let free = blob.byte()? as usize;
+        let value = blob.take(value_len)?.to_vec();
+        blob.take(free)?;
+        fields.push((key, value));
+    }
+
+    Ok(fields)
+}
+
+// Entries of one stream listpack, see the layout in t_stream.c.
+fn stream_entries(
+    master_id: StreamId,
+    elements: Vec<Element>,
+    entries: &mut Vec<StreamEntry>,
+) -> Result<(), RdbError> {
+    const DELETED: i64 = 1;
+    const SAME_FIELDS: i64 = 2;
+
+    let mut elements = elements.into_iter();
+    let mut next = || elements.next().ok_or(RdbError::Invalid("stream listpack"));
+    let count = |element: Element| {
+        u64::try_from(element.integer()?).map_err(|_| RdbError::Invalid("stream listpack"))
+    };
+
+    let valid = count(next()?)?;
+    let deleted = count(next()?)?;
+    let master_fields = (0..count(next()?)?)
+        .map(|_| next().map(Element::into_bytes))
+        .collect::<Result<Vec<_>, _>>()?;
+    next()?;
+
+    let total = valid
+        .checked_add(deleted)
+        .ok_or(RdbError::Invalid("stream listpack"))?;
+    for _ in 0..total {
+        let flags = next()?.integer()?;
+        let id = StreamId {
+            // the differences are signed, a later entry can have a lower sequence number
+            ms: master_id.ms.wrapping_add(next()?.integer()? as u64),
+            seq: master_id.seq.wrapping_add(next()?.integer()? as u64),
+        };
+
+        let fields = if flags & SAME_FIELDS != 0 {
+            master_fields
+                .iter()
+                .map(|field| Ok((field.clone(), next()?.into_bytes())))
+                .collect::<Result<Vec<_>, RdbError>>()?
+        } else {
+            (0..count(next()?)?)
+                .map(|_| Ok((next()?.into_bytes(), next()?.into_bytes())))
+                .collect::<Result<Vec<_>, RdbError>>()?
+        };
+        // number of elements of the entry, to iterate backwards
+        next()?;
+
+        if flags & DELETED == 0 {
+            entries.push(StreamEntry { id, fields });
+        }
+    }
+
+    Ok(())
+}
+
+fn backlen_size(len: usize) -> usize {
+    match len {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
+    }
+}
+
+fn write_len(output: &mut Vec<u8>, len: u64) {
+    match len {
+        0..=63 => output.push(len as u8),
+        64..=16383 => output.extend_from_slice(&[0x40 | (len >> 8) as u8, len as u8]),
+        16384..=0xffff_ffff => {
+            output.push(0x80);
+            output.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+        _ => {
+            output.push(0x81);
+            output.extend_from_slice(&len.to_be_bytes());
+        }
+    }
+}
+
+fn write_string(output: &mut Vec<u8>, data: &[u8]) {
+    write_len(output, data.len() as u64);
+    output.extend_from_slice(data);
+}
+
+fn write_stream_id(output: &mut Vec<u8>, id: StreamId) {
+    write_len(output, id.ms);
+    write_len(output, id.seq);
+}
+
+fn raw_stream_id(id: StreamId) -> [u8; 16] {
+    let mut data = [0; 16];
+    data[..8].copy_from_slice(&id.ms.to_be_bytes());
+    data[8..].copy_from_slice(&id.seq.to_be_bytes());
+    data
+}
+
+impl Element {
+    fn write(&self, output: &mut Vec<u8>) {
+        let start = output.len();
+
+        match *self {
+            Element::Integer(value @ 0..=127) => output.push(value as u8),
+            Element::Integer(value @ -4096..=4095) => {
+                let value = value as u16 & 0x1fff;
+                output.extend_from_slice(&[0xc0 | (value >> 8) as u8, value as u8]);
+            }
+            Element::Integer(value @ -32768..=32767) => {
+                output.push(0xf1);
+                output.extend_from_slice(&(value as i16).to_le_bytes());
+            }
+            Element::Integer(value @ -8388608..=8388607) => {
+                output.push(0xf2);
+                output.extend_from_slice(&(value as i32).to_le_bytes()[..3]);
+            }
+            Element::Integer(value @ -2147483648..=2147483647) => {
+                output.push(0xf3);
+                output.extend_from_slice(&(value as i32).to_le_bytes());
+            }
+            Element::Integer(value) => {
+                output.push(0xf4);
+                output.extend_from_slice(&value.to_le_bytes());
+            }
+            Element::Bytes(ref data) => {
+                match data.len() {
+                    len @ 0..=63 => output.push(0x80 | len as u8),
+                    len @ 64..=4095 => {
+                        output.extend_from_slice(&[0xe0 | (len >> 8) as u8, len as u8])
+                    }
+                    len => {
+                        output.push(0xf0);
+                        output.extend_from_slice(&(len as u32).to_le_bytes());
+                    }
+                }
+                output.extend_from_slice(data);
+            }
+        }
+
+        // 7 bits per byte with the most significant first, all but the first have the high bit set
+        let len = output.len() - start;
+        let size = backlen_size(len);
+        for index in 0..size {
+            let bits = ((len >> (7 * (size - 1 - index))) & 127) as u8;
+            output.push(if index == 0 { bits } else { bits | 128 });
+        }
+    }
+}
+
+fn write_listpack(elements: &[Element]) -> Vec<u8> {
+    let mut output = vec![0; 6];
+    for element in elements {
+        element.write(&mut output);
+    }
+    output.push(0xff);
+
+    let total = output.len() as u32;
+    output[..4].copy_from_slice(&total.to_le_bytes());
+    // u16::MAX means the listpack has to be walked to count the elements
+    let count = elements.len().min(u16::MAX as usize) as u16;
+    output[4..6].copy_from_slice(&count.to_le_bytes());
+    output
+}
+
+/// Writes the value type and the value in encodings `Input::object` reads and every Redis since
+/// 5.0 loads, returns the RDB version they need.
+pub(crate) fn write_value(output: &mut Vec<u8>, value: &RdbValue) -> Result<u16, RdbError> {
+    let empty = match value {
+        RdbValue::List(items) | RdbValue::Set(items) => items.is_empty(),
+        RdbValue::SortedSet(members) => members.is_empty(),
+        RdbValue::Hash(fields) => fields.is_empty(),
+        _ => false,
+    };
+    // Redis never stores an empty collection and refuses to load one
+    if empty {
+        return Err(RdbError::Invalid("empty value"));
+    }
+
+    match value {
+        RdbValue::String(data) => {
+            output.push(0);
+            write_string(output, data);
+        }
+        RdbValue::List(items) | RdbValue::Set(items) => {
+            output.push(if matches!(value, RdbValue::List(_)) {
+                1
+            } else {
+                2
+            });
+            write_len(output, items.len() as u64);
+            for item in items {
+                write_string(output, item);
+            }
+        }
+        RdbValue::SortedSet(members) => {
+            output.push(5);
+            write_len(output, members.len() as u64);
+            for (member, score) in members {
+                write_string(output, member);
+                output.extend_from_slice(&score.to_le_bytes());
+            }
+        }
+        RdbValue::Hash(fields) => {
+            output.push(4);
+            write_len(output, fields.len() as u64);
+            for (field, value) in fields {
+                write_string(output, field);
+                write_string(output, value);
+            }
+        }
+        RdbValue::Stream(stream) => return write_stream(output, stream),
+        RdbValue::Module(_) => return Err(RdbError::UnsupportedType(7)),
+    }
+
+    Ok(9)
+}
+
+// All entries go into a single listpack with the first entry as master entry.
+fn write_stream(output: &mut Vec<u8>, stream: &Stream) -> Result<u16, RdbError> {
+    if stream.entries.windows(2).any(|x| x[0].id >= x[1].id) {
+        return Err(RdbError::Invalid("stream entry order"));
+    }
+
+    // the newer types add fields that the older ones cannot hold
+    let value_type = if stream
+        .groups
+        .iter()
+        .flat_map(|group| &group.consumers)
+        .any(|consumer| consumer.active_time.is_some())
+    {
+        21
+    } else if stream.first_id.is_some()
+        || stream.max_deleted_id.is_some()
+        || stream.entries_added.is_some()
+        || stream
+            .groups
+            .iter()
+            .any(|group| group.entries_read.is_some())
+    {
+        19
+    } else {
+        15
+    };
+    output.push(value_type);
+
+    match stream.entries.first() {
+        None => write_len(output, 0),
+        Some(master) => {
+            let master_id = master.id;
+            let master_fields: Vec<&[u8]> = master
+                .fields
+                .iter()
+                .map(|(field, _)| field.as_slice())
+                .collect();
+
+            let mut elements = vec![
+                Element::Integer(stream.entries.len() as i64),
+                Element::Integer(0),
+                Element::Integer(master_fields.len() as i64),
+            ];
+            elements.extend(master_fields.iter().map(|x| Element::Bytes(x.to_vec())));
+            elements.push(Element::Integer(0));
+
+            for entry in &stream.entries {
+                let same_fields = entry.fields.len() == master_fields.len()
+                    && entry
+                        .fields
+                        .iter()
+                        .zip(&master_fields)
+                        .all(|((field, _), master)| field == master);
+
+                elements.push(Element::Integer(if same_fields { 2 } else { 0 }));
+                elements.push(Element::Integer(
+                    entry.id.ms.wrapping_sub(master_id.ms) as i64
+                ));
+                elements.push(Element::Integer(
+                    entry.id.seq.wrapping_sub(master_id.seq) as i64
+                ));
+
+                let values = entry
+                    .fields
+                    .iter()
+                    .map(|(_, value)| Element::Bytes(value.clone()));
+                if same_fields {
+                    elements.extend(values);
+                } else {
+                    elements.push(Element::Integer(entry.fields.len() as i64));
+                    for (field, value) in &entry.fields {
+                        elements.push(Element::Bytes(field.clone()));
+                        elements.push(Element::Bytes(value.clone()));
+                    }
+                }
+
+                // number of elements of the entry besides this one
+                let count = match same_fields {
+                    true => entry.fields.len() + 3,
+                    false => entry.fields.len() * 2 + 4,
+                };
+                elements.push(Element::Integer(count as i64));
+            }
+
+            write_len(output, 1);
+            write_string(output, &raw_stream_id(master_id));
+            write_string(output, &write_listpack(&elements));
+        }
+    }
+
+    write_len(output, stream.length);
+    write_stream_id(output, stream.last_id);
+    if value_type >= 19 {
+        let first_id = stream.entries.first().map(|x| x.id).unwrap_or_default();
+        write_stream_id(output, stream.first_id.unwrap_or(first_id));
+        write_stream_id(output, stream.max_deleted_id.unwrap_or_default());
+        write_len(output, stream.entries_added.unwrap_or(stream.length));
+    }
+
+    write_len(output, stream.groups.len() as u64);
+    for group in &stream.groups {
+        write_string(output, &group.name);
+        write_stream_id(output, group.last_id);
+        if value_type >= 19 {
+            write_len(output, group.entries_read.unwrap_or(u64::MAX));
+        }
+
+        write_len(output, group.pending.len() as u64);
+        for pending in &group.pending {
+            output.extend_from_slice(&raw_stream_id(pending.id));
+            output.extend_from_slice(&pending.delivery_time.to_le_bytes());
+            write_len(output, pending.delivery_count);
+        }
+
+        write_len(output, group.consumers.len() as u64);
+        for consumer in &group.consumers {
+            write_string(output, &consumer.name);
+            output.extend_from_slice(&consumer.seen_time.to_le_bytes());
+            if value_type >= 21 {
+                let active_time = consumer.active_time.unwrap_or(consumer.seen_time);
+                output.extend_from_slice(&active_time.to_le_bytes());
+            }
+            write_len(output, consumer.pending.len() as u64);
+            for id in &consumer.pending {
+                output.extend_from_slice(&raw_stream_id(*id));
+            }
+        }
+    }
+
+    Ok(match value_type {
+        15 => 9,
+        19 => 10,
+        _ => 11,
+    })
+}
+
+/// Streams the keys of an RDB snapshot, checking the CRC64 trailer at the end. The iterator
+/// ends after the first error.
+pub struct RdbReader<R> {
+    input: Input<R>,
+    version: u32,
+    database: u64,
+    done: bool,
+}
+
+impl RdbReader<std::io::BufReader<std::fs::File>> {
+    pub fn open(
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<RdbReader<std::io::BufReader<std::fs::File>>, RdbError> {
+        RdbReader::new(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+impl<R: Read> RdbReader<R> {
+    /// Reads the header, a plain `File` should be wrapped in a `BufReader`.
+    pub fn new(reader: R) -> Result<RdbReader<R>, RdbError> {
+        let mut input = Input::new(reader);
+        let header = input.array::<9>().map_err(|error| match error {
+            RdbError::Io(error) if error.kind() == ErrorKind::UnexpectedEof => {
+                RdbError::InvalidHeader
+            }
+            error => error,
+        })?;
+
+        let version = header
+            .strip_prefix(b"REDIS")
+            .and_then(|version| std::str::from_utf8(version).ok())
+            .and_then(|version| version.parse().ok())
+            .ok_or(RdbError::InvalidHeader)?;
+
+        Ok(RdbReader {
+            input,
+            version,
+            database: 0,
+            done: false,
+        })
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    fn read_event(&mut self) -> Result<Option<RdbEvent>, RdbError> {
+        let mut expire_at = None;
+
+        loop {
+            let opcode = self.input.byte()?;
+
+            match opcode {
+                0xff => {
+                    let actual = self.input.crc();
+                    if self.version >= 5 {
+                        let expected = self.input.unchecked_u64()?;
+                        // 0 means checksums were disabled when the file was written
+                        if expected != 0 && expected != actual {
+                            return Err(RdbError::ChecksumMismatch { expected, actual });
+                        }
+                    }
+                    return Ok(None);
+                }
+                0xfe => self.database = self.input.len()?,
+                0xfd => {
+                    expire_at = Some(u32::from_le_bytes(self.input.array()?) as u64 * 1000);
+                }
+                0xfc => expire_at = Some(self.input.millis()?),
+                // hash table sizes and per slot counts are only hints
+                0xfb => {
+                    self.input.len()?;
+                    self.input.len()?;
+                }
+                0xf4 => {
+                    self.input.len()?;
+                    self.input.len()?;
+                    self.input.len()?;
+                }
+                0xfa => {
+                    return Ok(Some(RdbEvent::Aux {
+                        key: self.input.string()?,
+                        value: self.input.string()?,
+                    }))
+                }
+                // LFU frequency and LRU idle time of the next key
+                0xf9 => {
+                    self.input.byte()?;
+                }
+                0xf8 => {
+                    self.input.len()?;
+                }
+                0xf7 => {
+                    self.input.len()?;
+                    self.input.len()?;
+                    self.input.len()?;
+                    self.input.skip_module_value()?;
+                }
+                0xf5 => return Ok(Some(RdbEvent::Function(self.input.string()?))),
+                0xf6 => return Err(RdbError::UnsupportedOpcode(opcode)),
+                value_type => {
+                    let key = self.input.string()?;
+                    let value = self.input.object(value_type)?;
+                    return Ok(Some(RdbEvent::Record(RdbRecord {
+                        database: self.database,
+                        key,
+                        value,
+                        expire_at,
+                    })));
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for RdbReader<R> {
+    type Item = Result<RdbEvent, RdbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.read_event().transpose();
+        self.done = !matches!(result, Some(Ok(_)));
+        result
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod encode {
+    // just enough of the format to write test files
+
+    pub fn len(len: usize) -> Vec<u8> {
+        match len {
+            0..=63 => vec![len as u8],
+            64..=16383 => vec![0x40 | (len >> 8) as u8, len as u8],
+            _ => [&[0x80][..], &(len as u32).to_be_bytes()].concat(),
+        }
+    }
+
+    pub fn string(data: &[u8]) -> Vec<u8> {
+        [len(data.len()), data.to_vec()].concat()
+    }
+
+    pub enum Item<'a> {
+        Str(&'a [u8]),
+        Int(u8),
+    }
+
+    pub fn listpack(items: &[Item]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for item in items {
+            let entry = match item {
+                Item::Str(data) => [&[0x80 | data.len() as u8][..], data].concat(),
+                Item::Int(value) => vec![*value],
+            };
+            body.push(entry.len() as u8);
+            body.splice(body.len() - 1..body.len() - 1, entry);
+        }
+        body.push(0xff);
+
+        let total = (body.len() + 6) as u32;
+        [
+            &total.to_le_bytes()[..],
+            &(items.len() as u16).to_le_bytes(),
+            &body,
+        ]
+        .concat()
+    }
+
+    pub fn rdb(body: &[u8]) -> Vec<u8> {
+        let mut data = [b"REDIS0011", body, &[0xff]].concat();
+        let crc = super::crc64(0, &data);
+        data.extend_from_slice(&crc.to_le_bytes());
+        data
+    }
+}
+
+#[test]
+fn rdb_crc64() {
+    assert_eq!(crc64(0, b"123456789"), 0xe9c6d914c4b8d9ca);
+}
+
+#[test]
+fn rdb_lzf() {
+    assert_eq!(
+        lzf_decompress(&[0x02, b'a', b'b', b'c', 0x20, 0x02], 6).unwrap(),
+        b"abcabc"
+    );
+    assert_eq!(
+        lzf_decompress(&[0x00, b'a', 0xe0, 0x00, 0x00], 10).unwrap(),
+        b"aaaaaaaaaa"
+    );
+    assert!(lzf_decompress(&[0x20, 0x05], 3).is_err());
+}
+
+#[test]
+fn rdb_reader_records() {
+    use encode::Item::{Int, Str};
+    use encode::*;
+
+    let ziplist = {
+        // "one" with score 1 and "two" with score 2 as a 4 bit immediate
+        let entries = [
+            &[0x00, 0x03][..],
+            b"one",
+            &[0x05, 0xf2],
+            &[0x02, 0x03],
+            b"two",
+            &[0x05, 0xf3],
+        ];
+        let body: Vec<u8> = entries.concat();
+        let total = (body.len() + 11) as u32;
+        [
+            &total.to_le_bytes()[..],
+            &[0, 0, 0, 0],
+            &4u16.to_le_bytes(),
+            &body,
+            &[0xff],
+        ]
+        .concat()
+    };
+    let intset = [
+        &2u32.to_le_bytes()[..],
+        &2u32.to_le_bytes(),
+        &1i16.to_le_bytes(),
+        &300i16.to_le_bytes(),
+    ]
+    .concat();
+
+    let body = [
+        &[0xfa][..],
+        &string(b"redis-ver"),
+        &string(b"7.2.4"),
+        &[0xfe, 0x02, 0xfb, 0x04, 0x01],
+        // expiring string
+        &[0xfc],
+        &1_700_000_000_000u64.to_le_bytes(),
+        &[0x00],
+        &string(b"greeting"),
+        &string(b"hello"),
+        // integer encoded string
+        &[0x00],
+        &string(b"counter"),
+        &[0xc1, 0x39, 0x30],
+        // compressed string
+        &[0x00],
+        &string(b"lzf"),
+        &[0xc3, 0x05, 0x0a, 0x00, b'a', 0xe0, 0x00, 0x00],
+        &[0x0b],
+        &string(b"ints"),
+        &string(&intset),
+        &[0x10],
+        &string(b"hash"),
+        &string(&listpack(&[
+            Str(b"field"),
+            Str(b"value"),
+            Str(b"n"),
+            Int(7),
+        ])),
+        &[0x0c],
+        &string(b"zset"),
+        &string(&ziplist),
+        &[0x12],
+        &string(b"list"),
+        &len(2),
+        &[0x02],
+        &string(&listpack(&[Str(b"a"), Int(1)])),
+        &[0x01],
+        &string(b"plain"),
+    ]
+    .concat();
+
+    let data = encode::rdb(&body);
+    let mut reader = RdbReader::new(data.as_slice()).unwrap();
+    assert_eq!(reader.version(), 11);
+    assert_eq!(
+        reader.next().unwrap().unwrap(),
+        RdbEvent::Aux {
+            key: b"redis-ver".to_vec(),
+            value: b"7.2.4".to_vec()
+        }
+    );
+
+    let records: Vec<RdbRecord> = reader
+        .map(|event| match event.unwrap() {
+            RdbEvent::Record(record) => record,
+            event => panic!("unexpected {:?}", event),
+        })
+        .collect();
+
+    assert_eq!(records.len(), 7);
+    assert!(records.iter().all(|record| record.database == 2));
+    assert_eq!(records[0].expire_at, Some(1_700_000_000_000));
+    assert_eq!(
+        records[0].to_commands(),
+        vec![
+            Command::new("SET").arg("greeting").arg("hello"),
+            Command::new("PEXPIREAT")
+                .arg("greeting")
+                .arg("1700000000000"),
+        ]
+    );
+    assert_eq!(records[1].value, RdbValue::String(b"12345".to_vec()));
+    assert_eq!(records[2].value, RdbValue::String(b"aaaaaaaaaa".to_vec()));
+    assert_eq!(
+        records[3].value,
+        RdbValue::Set(vec![b"1".to_vec(), b"300".to_vec()])
+    );
+    assert_eq!(
+        records[4].to_commands(),
+        vec![Command::new("HSET")
+            .arg("hash")
+            .arg("field")
+            .arg("value")
+            .arg("n")
+            .arg("7")]
+    );
+    assert_eq!(
+        records[5].to_commands(),
+        vec![Command::new("ZADD")
+            .arg("zset")
+            .arg("1")
+            .arg("one")
+            .arg("2")
+            .arg("two")]
+    );
+    assert_eq!(
+        records[6].value,
+        RdbValue::List(vec![b"a".to_vec(), b"1".to_vec(), b"plain".to_vec()])
+    );
+}
+
+#[test]
+fn rdb_reader_stream() {
+    use encode::Item::{Int, Str};
+    use encode::*;
+
+    let master_id = [&1000u64.to_be_bytes()[..], &0u64.to_be_bytes()].concat();
+    let entries = listpack(&[
+        // master entry: 3 valid, 1 deleted, fields ["a"]
+        Int(3),
+        Int(1),
+        Int(1),
+        Str(b"a"),
+        Int(0),
+        Int(2),
+        Int(0),
+        Int(0),
+        Int(1),
+        Int(4),
+        Int(2),
+        Int(1),
+        Int(0),
+        Str(b"2"),
+        Int(4),
+        Int(0),
+        Int(2),
+        Int(5),
+        Int(1),
+        Str(b"b"),
+        Str(b"x"),
+        Int(6),
+        // deleted
+        Int(3),
+        Int(3),
+        Int(0),
+        Str(b"9"),
+        Int(4),
+    ]);
+
+    let body = [
+        &[0x15][..],
+        &string(b"events"),
+        &len(1),
+        &string(&master_id),
+        &string(&entries),
+        // length, last id, first id, max deleted id, entries added
+        &[3, 0x43, 0xeb, 0, 0x43, 0xe8, 0, 0x43, 0xeb, 0, 4],
+        // one group with one pending entry
+        &len(1),
+        &string(b"group"),
+        &[0x43, 0xe9, 0, 2],
+        &len(1),
+        &master_id,
+        &5000u64.to_le_bytes(),
+        &len(2),
+        &len(1),
+        &string(b"consumer"),
+        &6000u64.to_le_bytes(),
+        &7000u64.to_le_bytes(),
+        &len(1),
+        &master_id,
+    ]
+    .concat();
+
+    let data = encode::rdb(&body);
+    let mut reader = RdbReader::new(data.as_slice()).unwrap();
+    let Some(Ok(RdbEvent::Record(record))) = reader.next() else {
+        panic!("expected a record");
+    };
+    assert!(reader.next().is_none());
+
+    assert_eq!(
+        record.to_commands(),
+        vec![
+            Command::new("XADD")
+                .arg("events")
+                .arg("1000-0")
+                .arg("a")
+                .arg("1"),
+            Command::new("XADD")
+                .arg("events")
+                .arg("1001-0")
+                .arg("a")
+                .arg("2"),
+            Command::new("XADD")
+                .arg("events")
+                .arg("1002-5")
+                .arg("b")
+                .arg("x"),
+            Command::new("XSETID")
+                .arg("events")
+                .arg("1003-0")
+                .arg("ENTRIESADDED")
+                .arg("4")
+                .arg("MAXDELETEDID")
+                .arg("1003-0"),
+            Command::new("XGROUP")
+                .arg("CREATE")
+                .arg("events")
+                .arg("group")
+                .arg("1001-0")
+                .arg("ENTRIESREAD")
+                .arg("2"),
+            Command::new("XGROUP")
+                .arg("CREATECONSUMER")
+                .arg("events")
+                .arg("group")
+                .arg("consumer"),
+            Command::new("XCLAIM")
+                .arg("events")
+                .arg("group")
+                .arg("consumer")
+                .arg("0")
+                .arg("1000-0")
+                .arg("TIME")
+                .arg("5000")
+                .arg("RETRYCOUNT")
+                .arg("2")
+                .arg("FORCE")
+                .arg("JUSTID")
+                .arg("LASTID")
+                .arg("1001-0"),
+        ]
+    );
+}
+
+#[test]
+fn rdb_listpack_round_trip() {
+    let elements = vec![
+        Element::Integer(100),
+        Element::Integer(-1),
+        Element::Integer(-5000),
+        Element::Integer(70000),
+        Element::Integer(-3_000_000_000),
+        Element::Bytes(b"short".to_vec()),
+        Element::Bytes(vec![b'x'; 200]),
+        Element::Bytes(vec![b'y'; 5000]),
+    ];
+    assert_eq!(listpack(&write_listpack(&elements)).unwrap(), elements);
+
+    let mut output = Vec::new();
+    write_len(&mut output, 1 << 40);
+    write_len(&mut output, 300);
+    let mut input = Input::new(output.as_slice());
+    assert_eq!(input.len().unwrap(), 1 << 40);
+    assert_eq!(input.len().unwrap(), 300);
+}
+
+#[test]
+fn rdb_reader_errors() {
+    assert!(matches!(
+        RdbReader::new(&b"RDB"[..]),
+        Err(RdbError::InvalidHeader)
+    ));
+
+    let mut data = encode::rdb(&[0x00, 0x01, b'k', 0x01, b'v']);
+    let last = data.len() - 1;
+    data[last] ^= 1;
+    assert!(matches!(
+        RdbReader::new(data.as_slice()).unwrap().next(),
+        Some(Ok(_))
+    ));
+    assert!(matches!(
+        RdbReader::new(data.as_slice()).unwrap().nth(1),
+        Some(Err(RdbError::ChecksumMismatch { .. }))
+    ));
+
+    let data = encode::rdb(&[0x1e, 0x01, b'k']);
+    assert!(matches!(
+        RdbReader::new(data.as_slice()).unwrap().next(),
+        Some(Err(RdbError::UnsupportedType(0x1e)))
+    ));
+
+    // negative entry counts in a stream listpack
+    let master_id = [0; 16];
+    for (valid, deleted) in [(&b"-1"[..], &b"1"[..]), (b"1", b"-9223372036854775808")] {
+        let entries = encode::listpack(&[
+            encode::Item::Str(valid),
+            encode::Item::Str(deleted),
+            encode::Item::Int(0),
+            encode::Item::Int(0),
+        ]);
+        let body = [
+            &[0x15][..],
+            &encode::string(b"s"),
+            &encode::len(1),
+            &encode::string(&master_id),
+            &encode::string(&entries),
+        ]
+        .concat();
+        let data = encode::rdb(&body);
+        assert!(matches!(
+            RdbReader::new(data.as_slice()).unwrap().next(),
+            Some(Err(RdbError::Invalid("stream listpack")))
+        ));
+    }
+
+    let data = &encode::rdb(&[0x00, 0x01, b'k', 0x05, b'v'])[..14];
+    assert!(matches!(
+        RdbReader::new(data).unwrap().next(),
+        Some(Err(RdbError::Io(_)))
+    ));
+}
@@ -0,0 +1,463 @@
+use std::fmt::Display;
+use std::io::{ErrorKind, Read};
+
+use crate::decoder::FrameBuffer;
+use crate::lexer::find_newline;
+use crate::{Command, Lexer, Parser, ParserOptions, RespErrorType};
+
+const READ_SIZE: usize = 64 * 1024;
+const EOF_MARK_LEN: usize = 40;
+
+#[derive(Debug)]
+pub enum ReplicationError {
+    Io(std::io::Error),
+    Parse(RespErrorType),
+    /// The master answered with an error, e.g. `-NOMASTERLINK`.
+    Rejected(Vec<u8>),
+    /// Data the replication protocol does not allow at this point.
+    Unexpected(Vec<u8>),
+}
+
+impl Display for ReplicationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            ReplicationError::Io(error) => write!(f, "{}", error),
+            ReplicationError::Parse(error_type) => write!(f, "{:?}", error_type),
+            ReplicationError::Rejected(error) => {
+                write!(f, "master replied {}", String::from_utf8_lossy(error))
+            }
+            ReplicationError::Unexpected(data) => write!(
+                f,
+                "unexpected replication data {}",
+                String::from_utf8_lossy(data)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplicationError {}
+
+impl From<std::io::Error> for ReplicationError {
+    fn from(error: std::io::Error) -> Self {
+        ReplicationError::Io(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationEvent {
+    /// The master sends a snapshot, the command stream continues from `offset`.
+    FullResync {
+        replid: String,
+        offset: u64,
+    },
+    /// Partial resync, commands follow right away. Older masters do not send the replid.
+    Continue {
+        replid: Option<String>,
+    },
+    /// `size` is `None` for a diskless transfer that ends with an `$EOF:` mark.
+    RdbStart {
+        size: Option<u64>,
+    },
+    RdbData(Vec<u8>),
+    RdbEnd,
+    /// `offset` is the replication offset after this command, as sent with `REPLCONF ACK`.
+    Command {
+        command: Command,
+        offset: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Handshake,
+    RdbHeader,
+    Rdb(u64),
+    RdbEofMark([u8; EOF_MARK_LEN]),
+    Commands,
+}
+
+/// `PSYNC <replid> <offset + 1>` to continue from `offset`, or `PSYNC ? -1` for a full
+/// resync.
+pub fn psync_command(replid: Option<&str>, offset: u64) -> Command {
+    match replid {
+        Some(replid) => Command::new("PSYNC")
+            .arg(replid)
+            .arg((offset + 1).to_string()),
+        None => Command::new("PSYNC").arg("?").arg("-1"),
+    }
+}
+
+/// Decodes what a master sends a replica after `PSYNC` (or `SYNC`). The snapshot bulk has no
+/// trailing `\r\n` and is returned in chunks, the commands after it are counted towards the
+/// replication offset.
+pub struct ReplicationDecoder<R> {
+    reader: R,
+    buffer: FrameBuffer,
+    state: State,
+    offset: u64,
+}
+
+impl<R: Read> ReplicationDecoder<R> {
+    pub fn new(reader: R) -> ReplicationDecoder<R> {
+        ReplicationDecoder {
+            reader,
+            buffer: FrameBuffer::new(ParserOptions::default(), READ_SIZE),
+            state: State::Handshake,
+            offset: 0,
+        }
+    }
+
+    pub fn set_parser_options(&mut self, options: ParserOptions) {
+        self.buffer.set_options(options);
+    }
+
+    /// Replication offset of the commands decoded so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// After `+CONTINUE` the stream goes on from the offset the replica asked for.
+    pub fn set_offset(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+
+    pub fn ack_command(&self) -> Command {
+        Command::new("REPLCONF")
+            .arg("ACK")
+            .arg(self.offset.to_string())
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// `None` once the master closed the connection between commands.
+    pub fn decode(&mut self) -> Result<Option<ReplicationEvent>, ReplicationError> {
+        loop {
+            if let Some(event) = self.decode_buffered()? {
+                return Ok(Some(event));
+            }
+
+            if self.buffer.read_from(&mut self.reader)? == 0 {
+                if self.state == State::Commands && self.buffer.pending().is_empty() {
+                    return Ok(None);
+                }
+                return Err(ReplicationError::Io(ErrorKind::UnexpectedEof.into()));
+            }
+        }
+    }
+
+    fn decode_buffered(&mut self) -> Result<Option<ReplicationEvent>, ReplicationError> {
+        match self.state.clone() {
+            State::Handshake | State::RdbHeader => {
+                // the master sends newlines while it is still preparing the snapshot
+                let newlines = self
+                    .buffer
+                    .pending()
+                    .iter()
+                    .take_while(|&&x| x == b'\n')
+                    .count();
+                self.buffer.consume(newlines);
+                let data = self.buffer.pending();
+
+                // `SYNC` replies with the snapshot right away
+                if self.state == State::Handshake && data.first() == Some(&b'$') {
+                    self.state = State::RdbHeader;
+                }
+
+                let Some(end) = find_newline(data) else {
+                    return Ok(None);
+                };
+                let line = data[..end].to_vec();
+                self.buffer.consume(end + 2);
+
+                if self.state == State::Handshake {
+                    self.handshake_reply(line).map(Some)
+                } else {
+                    self.rdb_header(line).map(Some)
+                }
+            }
+            State::Rdb(remaining) => {
+                if remaining == 0 {
+                    self.state = State::Commands;
+                    return Ok(Some(ReplicationEvent::RdbEnd));
+                }
+                let data = self.buffer.pending();
+                if data.is_empty() {
+                    return Ok(None);
+                }
+
+                let len = data
+                    .len()
+                    .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+                let chunk = data[..len].to_vec();
+                self.buffer.consume(len);
+                self.state = State::Rdb(remaining - len as u64);
+                Ok(Some(ReplicationEvent::RdbData(chunk)))
+            }
+            State::RdbEofMark(mark) => {
+                let data = self.buffer.pending();
+                if data.starts_with(&mark) {
+                    self.buffer.consume(EOF_MARK_LEN);
+                    self.state = State::Commands;
+                    return Ok(Some(ReplicationEvent::RdbEnd));
+                }
+
+                // everything before the mark, or all but what could be the start of the mark
+                let len = match memchr::memmem::find(data, &mark) {
+                    Some(position) => position,
+                    None => data.len().saturating_sub(EOF_MARK_LEN - 1),
+                };
+                if len == 0 {
+                    return Ok(None);
+                }
+
+                let chunk = data[..len].to_vec();
+                self.buffer.consume(len);
+                Ok(Some(ReplicationEvent::RdbData(chunk)))
+            }
+            State::Commands => {
+                let parse_error =
+                    |error: crate::ParseError| ReplicationError::Parse(error.error_type());
+                let Some(len) = self.buffer.frame_len().map_err(parse_error)? else {
+                    return Ok(None);
+                };
+
+                let data = &self.buffer.pending()[..len];
+                let frame = Parser::new_with_options(Lexer::new(data), self.buffer.options())
+                    .parse()
+                    .map_err(parse_error)?
+                    .to_owned();
+                let command = Command::try_from(frame)
+                    .map_err(|_| ReplicationError::Unexpected(data.to_vec()))?;
+
+                self.buffer.consume(len);
+                self.offset += len as u64;
+                Ok(Some(ReplicationEvent::Command {
+                    command,
+                    offset: self.offset,
+                }))
+            }
+        }
+    }
+
+    fn handshake_reply(&mut self, line: Vec<u8>) -> Result<ReplicationEvent, ReplicationError> {
+        if let Some(error) = line.strip_prefix(b"-") {
+            return Err(ReplicationError::Rejected(error.to_vec()));
+        }
+
+        let text =
+            std::str::from_utf8(&line).map_err(|_| ReplicationError::Unexpected(line.clone()))?;
+        let mut words = text.split(' ');
+
+        match words.next() {
+            Some("+FULLRESYNC") => {
+                let replid = words.next().map(String::from);
+                let offset = words.next().and_then(|x| x.parse().ok());
+                let (Some(replid), Some(offset)) = (replid, offset) else {
+                    return Err(ReplicationError::Unexpected(line));
+                };
+
+                self.offset = offset;
+                self.state = State::RdbHeader;
+                Ok(ReplicationEvent::FullResync { replid, offset })
+            }
+            Some("+CONTINUE") => {
+                self.state = State::Commands;
+                Ok(ReplicationEvent::Continue {
+                    replid: words.next().map(String::from),
+                })
+            }
+            _ => Err(ReplicationError::Unexpected(line)),
+        }
+    }
+
+    fn rdb_header(&mut self, line: Vec<u8>) -> Result<ReplicationEvent, ReplicationError> {
+        if let Some(mark) = line.strip_prefix(b"$EOF:") {
+            let mark = <[u8; EOF_MARK_LEN]>::try_from(mark)
+                .map_err(|_| ReplicationError::Unexpected(line.clone()))?;
+            self.state = State::RdbEofMark(mark);
+            return Ok(ReplicationEvent::RdbStart { size: None });
+        }
+
+        let size = line
+            .strip_prefix(b"$")
+            .and_then(|size| std::str::from_utf8(size).ok())
+            .and_then(|size| size.parse().ok())
+            .ok_or_else(|| ReplicationError::Unexpected(line.clone()))?;
+
+        self.state = State::Rdb(size);
+        Ok(ReplicationEvent::RdbStart { size: Some(size) })
+    }
+}
+
+// hands out a few bytes per read so the framing is split at every possible position
+#[cfg(test)]
+struct Trickle<'a>(&'a [u8], usize);
+
+#[cfg(test)]
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.0.len().min(buf.len()).min(self.1);
+        buf[..len].copy_from_slice(&self.0[..len]);
+        self.0 = &self.0[len..];
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+fn collect_events(reader: impl Read) -> (Vec<ReplicationEvent>, Vec<u8>) {
+    let mut decoder = ReplicationDecoder::new(reader);
+    let mut events = Vec::new();
+    let mut rdb = Vec::new();
+
+    while let Some(event) = decoder.decode().unwrap() {
+        match event {
+            ReplicationEvent::RdbData(data) => rdb.extend(data),
+            event => events.push(event),
+        }
+    }
+
+    (events, rdb)
+}
+
+#[test]
+fn replication_full_resync() {
+    let data = b"+FULLRESYNC 8de1787ba490483314a4d30f1c628bc5025eb761 100\r\n\n\n$9\r\nREDIS0011\
+        *1\r\n$4\r\nPING\r\n*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n";
+
+    for chunk in [1, 7, 1024] {
+        let (events, rdb) = collect_events(Trickle(data, chunk));
+
+        assert_eq!(rdb, b"REDIS0011");
+        assert_eq!(
+            events,
+            vec![
+                ReplicationEvent::FullResync {
+                    replid: "8de1787ba490483314a4d30f1c628bc5025eb761".to_string(),
+                    offset: 100
+                },
+                ReplicationEvent::RdbStart { size: Some(9) },
+                ReplicationEvent::RdbEnd,
+                ReplicationEvent::Command {
+                    command: Command::new("PING"),
+                    offset: 114
+                },
+                ReplicationEvent::Command {
+                    command: Command::new("SET").arg("a").arg("1"),
+                    offset: 141
+                },
+            ]
+        );
+    }
+}
+
+#[test]
+fn replication_diskless_and_continue() {
+    let mark = [b'x'; EOF_MARK_LEN];
+    let mut data = b"+FULLRESYNC abc 0\r\n$EOF:".to_vec();
+    data.extend_from_slice(&mark);
+    data.extend_from_slice(b"\r\nREDIS0011xxxx\xff");
+    data.extend_from_slice(&mark);
+    data.extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+
+    for chunk in [1, 13, 1024] {
+        let (events, rdb) = collect_events(Trickle(&data, chunk));
+
+        assert_eq!(rdb, b"REDIS0011xxxx\xff");
+        assert_eq!(events[1], ReplicationEvent::RdbStart { size: None });
+        assert_eq!(
+            events.last(),
+            Some(&ReplicationEvent::Command {
+                command: Command::new("PING"),
+                offset: 14
+            })
+        );
+    }
+
+    assert_eq!(
+        psync_command(Some("abc"), 49),
+        Command::new("PSYNC").arg("abc").arg("50")
+    );
+    let mut decoder = ReplicationDecoder::new(&b"+CONTINUE\r\n*1\r\n$4\r\nPING\r\n"[..]);
+    decoder.set_offset(50);
+    assert_eq!(
+        decoder.decode().unwrap(),
+        Some(ReplicationEvent::Continue { replid: None })
+    );
+    decoder.decode().unwrap();
+    assert_eq!(decoder.offset(), 64);
+    assert_eq!(
+        decoder.ack_command(),
+        Command::new("REPLCONF").arg("ACK").arg("64")
+    );
+}
+
+#[test]
+fn replication_sync_and_errors() {
+    let (events, rdb) = collect_events(&b"$5\r\nREDIS*1\r\n$4\r\nPING\r\n"[..]);
+    assert_eq!(rdb, b"REDIS");
+    assert_eq!(events.len(), 3);
+
+    let mut decoder = ReplicationDecoder::new(&b"-NOMASTERLINK Can't SYNC\r\n"[..]);
+    assert!(matches!(
+        decoder.decode(),
+        Err(ReplicationError::Rejected(error)) if error.starts_with(b"NOMASTERLINK")
+    ));
+
+    let mut decoder = ReplicationDecoder::new(&b"+FULLRESYNC abc 0\r\n$10\r\nREDIS"[..]);
+    decoder.decode().unwrap();
+    decoder.decode().unwrap();
+    decoder.decode().unwrap();
+    assert!(matches!(
+        decoder.decode(),
+        Err(ReplicationError::Io(error)) if error.kind() == ErrorKind::UnexpectedEof
+    ));
+
+    let mut decoder = ReplicationDecoder::new(&b"+CONTINUE\r\n:1\r\n"[..]);
+    decoder.decode().unwrap();
+    assert!(matches!(
+        decoder.decode(),
+        Err(ReplicationError::Unexpected(_))
+    ));
+}
+
+#[test]
+fn replication_large_command() {
+    // counts the reads, each one returns as much as fits
+    struct Reads<'a>(&'a [u8], usize);
+
+    impl Read for Reads<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.1 += 1;
+            self.0.read(buf)
+        }
+    }
+
+    let value = vec![b'x'; 4 * 1024 * 1024];
+    let mut data = b"+CONTINUE\r\n".to_vec();
+    Command::new("SET")
+        .arg("key")
+        .arg(value.as_slice())
+        .write_to_vec(&mut data);
+    let command_len = data.len() as u64 - 11;
+
+    let mut reads = Reads(&data, 0);
+    let mut decoder = ReplicationDecoder::new(&mut reads);
+    decoder.decode().unwrap();
+    match decoder.decode().unwrap() {
+        Some(ReplicationEvent::Command { command, offset }) => {
+            assert_eq!(command.args()[2], value);
+            assert_eq!(offset, command_len);
+        }
+        event => panic!("expected a command, got {:?}", event),
+    }
+    assert_eq!(decoder.decode().unwrap(), None);
+
+    // the reads grow once the size of the bulk string is known
+    assert!(reads.1 < 16, "{} reads", reads.1);
+}
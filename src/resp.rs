@@ -0,0 +1,358 @@
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{RespType, RespTypeRef, Value};
+
+/// A frame that either borrows from the input or owns its data, borrowed and owned parts can
+/// be mixed in one tree. `RespTypeRef` and `RespType` convert into it and back.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Resp<'a> {
+    SimpleString(Cow<'a, [u8]>),
+    Error(Cow<'a, [u8]>),
+    Integer(i64),
+    BulkString(Cow<'a, [u8]>),
+    NullString,
+    Array(Vec<Resp<'a>>),
+    NullArray,
+    Null,
+    Boolean(bool),
+    Double(f64),
+    BigNumber(Cow<'a, [u8]>),
+    BulkError(Cow<'a, [u8]>),
+    VerbatimString([u8; 3], Cow<'a, [u8]>),
+    Map(Vec<(Resp<'a>, Resp<'a>)>),
+    Set(Vec<Resp<'a>>),
+    Push(Vec<Resp<'a>>),
+}
+
+impl<'a> Resp<'a> {
+    pub fn into_owned(self) -> Resp<'static> {
+        fn convert(data: Cow<'_, [u8]>) -> Cow<'static, [u8]> {
+            Cow::Owned(data.into_owned())
+        }
+
+        fn convert_all(items: Vec<Resp<'_>>) -> Vec<Resp<'static>> {
+            items.into_iter().map(|x| x.into_owned()).collect()
+        }
+
+        match self {
+            Resp::SimpleString(x) => Resp::SimpleString(convert(x)),
+            Resp::Error(x) => Resp::Error(convert(x)),
+            Resp::Integer(x) => Resp::Integer(x),
+            Resp::BulkString(x) => Resp::BulkString(convert(x)),
+            Resp::NullString => Resp::NullString,
+            Resp::Array(x) => Resp::Array(convert_all(x)),
+            Resp::NullArray => Resp::NullArray,
+            Resp::Null => Resp::Null,
+            Resp::Boolean(x) => Resp::Boolean(x),
+            Resp::Double(x) => Resp::Double(x),
+            Resp::BigNumber(x) => Resp::BigNumber(convert(x)),
+            Resp::BulkError(x) => Resp::BulkError(convert(x)),
+            Resp::VerbatimString(format, x) => Resp::VerbatimString(format, convert(x)),
+            Resp::Map(x) => Resp::Map(
+                x.into_iter()
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect(),
+            ),
+            Resp::Set(x) => Resp::Set(convert_all(x)),
+            Resp::Push(x) => Resp::Push(convert_all(x)),
+        }
+    }
+
+    pub fn as_resp_type_ref(&self) -> RespTypeRef<'_> {
+        fn convert<'b>(items: &'b [Resp<'_>]) -> Vec<RespTypeRef<'b>> {
+            items.iter().map(|x| x.as_resp_type_ref()).collect()
+        }
+
+        match self {
+            Resp::SimpleString(x) => RespTypeRef::SimpleString(x),
+            Resp::Error(x) => RespTypeRef::Error(x),
+            Resp::Integer(x) => RespTypeRef::Integer(*x),
+            Resp::BulkString(x) => RespTypeRef::BulkString(x),
+            Resp::NullString => RespTypeRef::NullString,
+            Resp::Array(x) => RespTypeRef::Array(convert(x)),
+            Resp::NullArray => RespTypeRef::NullArray,
+            Resp::Null => RespTypeRef::Null,
+            Resp::Boolean(x) => RespTypeRef::Boolean(*x),
+            Resp::Double(x) => RespTypeRef::Double(*x),
+            Resp::BigNumber(x) => RespTypeRef::BigNumber(x),
+            Resp::BulkError(x) => RespTypeRef::BulkError(x),
+            Resp::VerbatimString(format, x) => RespTypeRef::VerbatimString(*format, x),
+            Resp::Map(x) => RespTypeRef::Map(
+                x.iter()
+                    .map(|(key, value)| (key.as_resp_type_ref(), value.as_resp_type_ref()))
+                    .collect(),
+            ),
+            Resp::Set(x) => RespTypeRef::Set(convert(x)),
+            Resp::Push(x) => RespTypeRef::Push(convert(x)),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        use Resp::*;
+
+        matches!(self, NullString | NullArray | Null)
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        use Resp::*;
+
+        match self {
+            SimpleString(data) => Some(data),
+            BulkString(data) => Some(data),
+            VerbatimString(_, data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn as_error_bytes(&self) -> Option<&[u8]> {
+        use Resp::*;
+
+        match self {
+            Error(error) => Some(error),
+            BulkError(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&str> {
+        self.as_bytes().and_then(|x| core::str::from_utf8(x).ok())
+    }
+
+    pub fn as_error_string(&self) -> Option<&str> {
+        self.as_error_bytes()
+            .and_then(|x| core::str::from_utf8(x).ok())
+    }
+
+    pub fn into_bytes(self) -> Option<Cow<'a, [u8]>> {
+        use Resp::*;
+
+        match self {
+            SimpleString(data) => Some(data),
+            BulkString(data) => Some(data),
+            VerbatimString(_, data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn into_error_bytes(self) -> Option<Cow<'a, [u8]>> {
+        use Resp::*;
+
+        match self {
+            Error(error) => Some(error),
+            BulkError(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    pub fn into_value(self) -> Result<Value, Value> {
+        use Resp::*;
+
+        // valid UTF-8 becomes a `String`, reusing owned buffers
+        fn text_or_bytes(data: Cow<'_, [u8]>) -> Value {
+            match String::from_utf8(data.into_owned()) {
+                Ok(text) => Value::String(text),
+                Err(error) => Value::Bytes(error.into_bytes()),
+            }
+        }
+
+        match self {
+            NullString | NullArray | Null => Ok(Value::Null),
+            Error(data) | BulkError(data) => Err(text_or_bytes(data)),
+            SimpleString(data) | BulkString(data) | VerbatimString(_, data) => {
+                Ok(text_or_bytes(data))
+            }
+            Integer(data) => Ok(Value::Int(data)),
+            Boolean(data) => Ok(Value::Bool(data)),
+            Double(data) => Ok(Value::Double(data)),
+            BigNumber(data) => Ok(Value::String(String::from_utf8_lossy(&data).into_owned())),
+            Array(data) | Set(data) | Push(data) => {
+                let converted: Result<Vec<Value>, Value> =
+                    data.into_iter().map(Resp::into_value).collect();
+                Ok(Value::Array(converted?))
+            }
+            Map(data) => {
+                let converted: Result<Vec<(Value, Value)>, Value> = data
+                    .into_iter()
+                    .map(|(key, value)| Ok((key.into_value()?, value.into_value()?)))
+                    .collect();
+                Ok(Value::Map(converted?))
+            }
+        }
+    }
+}
+
+impl<'a> From<RespTypeRef<'a>> for Resp<'a> {
+    fn from(val: RespTypeRef<'a>) -> Self {
+        fn convert(items: Vec<RespTypeRef<'_>>) -> Vec<Resp<'_>> {
+            items.into_iter().map(Resp::from).collect()
+        }
+
+        match val {
+            RespTypeRef::SimpleString(x) => Resp::SimpleString(Cow::Borrowed(x)),
+            RespTypeRef::Error(x) => Resp::Error(Cow::Borrowed(x)),
+            RespTypeRef::Integer(x) => Resp::Integer(x),
+            RespTypeRef::BulkString(x) => Resp::BulkString(Cow::Borrowed(x)),
+            RespTypeRef::NullString => Resp::NullString,
+            RespTypeRef::Array(x) => Resp::Array(convert(x)),
+            RespTypeRef::NullArray => Resp::NullArray,
+            RespTypeRef::Null => Resp::Null,
+            RespTypeRef::Boolean(x) => Resp::Boolean(x),
+            RespTypeRef::Double(x) => Resp::Double(x),
+            RespTypeRef::BigNumber(x) => Resp::BigNumber(Cow::Borrowed(x)),
+            RespTypeRef::BulkError(x) => Resp::BulkError(Cow::Borrowed(x)),
+            RespTypeRef::VerbatimString(format, x) => {
+                Resp::VerbatimString(format, Cow::Borrowed(x))
+            }
+            RespTypeRef::Map(x) => Resp::Map(
+                x.into_iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect(),
+            ),
+            RespTypeRef::Set(x) => Resp::Set(convert(x)),
+            RespTypeRef::Push(x) => Resp::Push(convert(x)),
+            // a single chunk can still be borrowed, otherwise the chunks have to be joined
+            RespTypeRef::StreamedString(x) if x.len() == 1 => Resp::BulkString(Cow::Borrowed(x[0])),
+            RespTypeRef::StreamedString(x) => Resp::BulkString(Cow::Owned(x.concat())),
+        }
+    }
+}
+
+impl From<RespType> for Resp<'static> {
+    fn from(val: RespType) -> Self {
+        fn convert(items: Vec<RespType>) -> Vec<Resp<'static>> {
+            items.into_iter().map(Resp::from).collect()
+        }
+
+        match val {
+            RespType::SimpleString(x) => Resp::SimpleString(Cow::Owned(x)),
+            RespType::Error(x) => Resp::Error(Cow::Owned(x)),
+            RespType::Integer(x) => Resp::Integer(x),
+            RespType::BulkString(x) => Resp::BulkString(Cow::Owned(x)),
+            RespType::NullString => Resp::NullString,
+            RespType::Array(x) => Resp::Array(convert(x)),
+            RespType::NullArray => Resp::NullArray,
+            RespType::Null => Resp::Null,
+            RespType::Boolean(x) => Resp::Boolean(x),
+            RespType::Double(x) => Resp::Double(x),
+            RespType::BigNumber(x) => Resp::BigNumber(Cow::Owned(x)),
+            RespType::BulkError(x) => Resp::BulkError(Cow::Owned(x)),
+            RespType::VerbatimString(format, x) => Resp::VerbatimString(format, Cow::Owned(x)),
+            RespType::Map(x) => Resp::Map(
+                x.into_iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect(),
+            ),
+            RespType::Set(x) => Resp::Set(convert(x)),
+            RespType::Push(x) => Resp::Push(convert(x)),
+        }
+    }
+}
+
+impl From<Resp<'_>> for RespType {
+    fn from(val: Resp<'_>) -> Self {
+        fn convert(items: Vec<Resp<'_>>) -> Vec<RespType> {
+            items.into_iter().map(RespType::from).collect()
+        }
+
+        match val {
+            Resp::SimpleString(x) => RespType::SimpleString(x.into_owned()),
+            Resp::Error(x) => RespType::Error(x.into_owned()),
+            Resp::Integer(x) => RespType::Integer(x),
+            Resp::BulkString(x) => RespType::BulkString(x.into_owned()),
+            Resp::NullString => RespType::NullString,
+            Resp::Array(x) => RespType::Array(convert(x)),
+            Resp::NullArray => RespType::NullArray,
+            Resp::Null => RespType::Null,
+            Resp::Boolean(x) => RespType::Boolean(x),
+            Resp::Double(x) => RespType::Double(x),
+            Resp::BigNumber(x) => RespType::BigNumber(x.into_owned()),
+            Resp::BulkError(x) => RespType::BulkError(x.into_owned()),
+            Resp::VerbatimString(format, x) => RespType::VerbatimString(format, x.into_owned()),
+            Resp::Map(x) => RespType::Map(
+                x.into_iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect(),
+            ),
+            Resp::Set(x) => RespType::Set(convert(x)),
+            Resp::Push(x) => RespType::Push(convert(x)),
+        }
+    }
+}
+
+impl From<Resp<'_>> for Result<Value, Value> {
+    fn from(val: Resp<'_>) -> Self {
+        val.into_value()
+    }
+}
+
+#[test]
+fn resp_mixes_borrowed_and_owned() {
+    let data = b"*2\r\n$3\r\nfoo\r\n$?\r\n;2\r\nba\r\n;1\r\nr\r\n;0\r\n";
+    let resp = crate::bytes_to_resp(data).unwrap();
+
+    match &resp {
+        Resp::Array(items) => {
+            assert!(matches!(&items[0], Resp::BulkString(Cow::Borrowed(b"foo"))));
+            assert!(matches!(&items[1], Resp::BulkString(Cow::Owned(x)) if x == b"bar"));
+        }
+        _ => panic!("expected an array"),
+    }
+
+    let owned: Resp<'static> = resp.clone().into_owned();
+    assert_eq!(owned, resp);
+    assert_eq!(
+        RespType::from(owned),
+        crate::bytes_to_resp_type(data).unwrap()
+    );
+}
+
+#[test]
+fn resp_parsed_directly() {
+    let inputs: [&[u8]; 6] = [
+        b"%2\r\n+a\r\n:1\r\n+b\r\n~1\r\n#t\r\n",
+        b"*?\r\n:1\r\n*?\r\n.\r\n.\r\n",
+        b"%?\r\n+a\r\n,1.5\r\n.\r\n",
+        b">2\r\n=8\r\ntxt:text\r\n!3\r\nERR\r\n",
+        b"*2\r\n*-1\r\n$-1\r\n",
+        b"*1\r\n.\r\n",
+    ];
+
+    for data in inputs {
+        let expected = crate::Parser::new_from_bytes(data)
+            .parse()
+            .map(Resp::from)
+            .map_err(|x| x.error_type());
+        assert_eq!(
+            crate::bytes_to_resp(data).map_err(|x| x.error_type()),
+            expected
+        );
+    }
+
+    let value = crate::bytes_to_resp(b"*3\r\n+OK\r\n-\xff\r\n%1\r\n:1\r\n_\r\n").unwrap();
+    assert_eq!(value.into_value(), Err(Value::Bytes(b"\xff".to_vec())));
+    assert_eq!(
+        crate::bytes_to_resp(b"*2\r\n$2\r\nab\r\n%1\r\n:1\r\n_\r\n")
+            .unwrap()
+            .into_value(),
+        Ok(Value::Array(vec![
+            Value::String("ab".into()),
+            Value::Map(vec![(Value::Int(1), Value::Null)])
+        ]))
+    );
+}
+
+#[test]
+fn resp_shared_accessors() {
+    let borrowed = Resp::from(RespTypeRef::BulkString(b"text"));
+    let owned = Resp::from(RespType::BulkString(b"text".to_vec()));
+
+    assert_eq!(borrowed.as_string(), Some("text"));
+    assert_eq!(owned.as_string(), Some("text"));
+    assert_eq!(borrowed.as_resp_type_ref(), owned.as_resp_type_ref());
+    assert_eq!(
+        Resp::from(RespType::BulkError(b"ERR".to_vec())).as_error_string(),
+        Some("ERR")
+    );
+}
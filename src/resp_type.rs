@@ -1,6 +1,11 @@
+use alloc::string::String;
+#[cfg(test)]
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
 use crate::Value;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum RespTypeRef<'a> {
     SimpleString(&'a [u8]),
     Error(&'a [u8]),
@@ -9,6 +14,17 @@ pub enum RespTypeRef<'a> {
     NullString,
     Array(Vec<RespTypeRef<'a>>),
     NullArray,
+    Null,
+    Boolean(bool),
+    Double(f64),
+    BigNumber(&'a [u8]),
+    BulkError(&'a [u8]),
+    VerbatimString([u8; 3], &'a [u8]),
+    Map(Vec<(RespTypeRef<'a>, RespTypeRef<'a>)>),
+    Set(Vec<RespTypeRef<'a>>),
+    Push(Vec<RespTypeRef<'a>>),
+    /// RESP3 streamed string (`$?`), the chunks are joined into a `BulkString` by `to_owned`.
+    StreamedString(Vec<&'a [u8]>),
 }
 
 impl<'a> RespTypeRef<'a> {
@@ -19,19 +35,29 @@ impl<'a> RespTypeRef<'a> {
             RespTypeRef::Integer(x) => RespType::Integer(*x),
             RespTypeRef::BulkString(x) => RespType::BulkString(x.to_vec()),
             RespTypeRef::NullString => RespType::NullString,
-            RespTypeRef::Array(x) => RespType::Array(x.into_iter().map(|y| y.to_owned()).collect()),
+            RespTypeRef::Array(x) => RespType::Array(x.iter().map(|y| y.to_owned()).collect()),
             RespTypeRef::NullArray => RespType::NullArray,
+            RespTypeRef::Null => RespType::Null,
+            RespTypeRef::Boolean(x) => RespType::Boolean(*x),
+            RespTypeRef::Double(x) => RespType::Double(*x),
+            RespTypeRef::BigNumber(x) => RespType::BigNumber(x.to_vec()),
+            RespTypeRef::BulkError(x) => RespType::BulkError(x.to_vec()),
+            RespTypeRef::VerbatimString(format, x) => RespType::VerbatimString(*format, x.to_vec()),
+            RespTypeRef::Map(x) => RespType::Map(
+                x.iter()
+                    .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                    .collect(),
+            ),
+            RespTypeRef::Set(x) => RespType::Set(x.iter().map(|y| y.to_owned()).collect()),
+            RespTypeRef::Push(x) => RespType::Push(x.iter().map(|y| y.to_owned()).collect()),
+            RespTypeRef::StreamedString(x) => RespType::BulkString(x.concat()),
         }
     }
 
     pub fn is_null(&self) -> bool {
         use RespTypeRef::*;
 
-        match self {
-            NullString => true,
-            NullArray => true,
-            _ => false,
-        }
+        matches!(self, NullString | NullArray | Null)
     }
 
     pub fn as_bytes(&self) -> Option<&[u8]> {
@@ -40,28 +66,30 @@ impl<'a> RespTypeRef<'a> {
         match self {
             SimpleString(data) => Some(data),
             BulkString(data) => Some(data),
+            VerbatimString(_, data) => Some(data),
+            StreamedString(chunks) if chunks.len() == 1 => Some(chunks[0]),
+            StreamedString(chunks) if chunks.is_empty() => Some(b""),
             _ => None,
         }
     }
 
     pub fn as_error_bytes(&self) -> Option<&[u8]> {
-        if let RespTypeRef::Error(error) = self {
-            Some(error)
-        } else {
-            None
+        use RespTypeRef::*;
+
+        match self {
+            Error(error) => Some(error),
+            BulkError(error) => Some(error),
+            _ => None,
         }
     }
 
     pub fn as_string(&self) -> Option<&str> {
-        self.as_bytes()
-            .map(|x| std::str::from_utf8(x).ok())
-            .flatten()
+        self.as_bytes().and_then(|x| core::str::from_utf8(x).ok())
     }
 
     pub fn as_error_string(&self) -> Option<&str> {
         self.as_error_bytes()
-            .map(|x| std::str::from_utf8(x).ok())
-            .flatten()
+            .and_then(|x| core::str::from_utf8(x).ok())
     }
 }
 
@@ -74,6 +102,15 @@ pub enum RespType {
     NullString,
     Array(Vec<RespType>),
     NullArray,
+    Null,
+    Boolean(bool),
+    Double(f64),
+    BigNumber(Vec<u8>),
+    BulkError(Vec<u8>),
+    VerbatimString([u8; 3], Vec<u8>),
+    Map(Vec<(RespType, RespType)>),
+    Set(Vec<RespType>),
+    Push(Vec<RespType>),
 }
 
 impl RespType {
@@ -84,21 +121,28 @@ impl RespType {
             RespType::Integer(x) => RespTypeRef::Integer(*x),
             RespType::BulkString(x) => RespTypeRef::BulkString(x),
             RespType::NullString => RespTypeRef::NullString,
-            RespType::Array(x) => {
-                RespTypeRef::Array(x.into_iter().map(|y| y.as_referenced()).collect())
-            }
+            RespType::Array(x) => RespTypeRef::Array(x.iter().map(|y| y.as_referenced()).collect()),
             RespType::NullArray => RespTypeRef::NullArray,
+            RespType::Null => RespTypeRef::Null,
+            RespType::Boolean(x) => RespTypeRef::Boolean(*x),
+            RespType::Double(x) => RespTypeRef::Double(*x),
+            RespType::BigNumber(x) => RespTypeRef::BigNumber(x),
+            RespType::BulkError(x) => RespTypeRef::BulkError(x),
+            RespType::VerbatimString(format, x) => RespTypeRef::VerbatimString(*format, x),
+            RespType::Map(x) => RespTypeRef::Map(
+                x.iter()
+                    .map(|(key, value)| (key.as_referenced(), value.as_referenced()))
+                    .collect(),
+            ),
+            RespType::Set(x) => RespTypeRef::Set(x.iter().map(|y| y.as_referenced()).collect()),
+            RespType::Push(x) => RespTypeRef::Push(x.iter().map(|y| y.as_referenced()).collect()),
         }
     }
 
     pub fn is_null(&self) -> bool {
         use RespType::*;
 
-        match self {
-            NullString => true,
-            NullArray => true,
-            _ => false,
-        }
+        matches!(self, NullString | NullArray | Null)
     }
 
     pub fn as_bytes(&self) -> Option<&[u8]> {
@@ -107,28 +151,28 @@ impl RespType {
         match self {
             SimpleString(data) => Some(data),
             BulkString(data) => Some(data),
+            VerbatimString(_, data) => Some(data),
             _ => None,
         }
     }
 
     pub fn as_error_bytes(&self) -> Option<&[u8]> {
-        if let RespType::Error(error) = self {
-            Some(error)
-        } else {
-            None
+        use RespType::*;
+
+        match self {
+            Error(error) => Some(error),
+            BulkError(error) => Some(error),
+            _ => None,
         }
     }
 
     pub fn as_string(&self) -> Option<&str> {
-        self.as_bytes()
-            .map(|x| std::str::from_utf8(x).ok())
-            .flatten()
+        self.as_bytes().and_then(|x| core::str::from_utf8(x).ok())
     }
 
     pub fn as_error_string(&self) -> Option<&str> {
         self.as_error_bytes()
-            .map(|x| std::str::from_utf8(x).ok())
-            .flatten()
+            .and_then(|x| core::str::from_utf8(x).ok())
     }
 
     pub fn into_bytes(self) -> Option<Vec<u8>> {
@@ -137,28 +181,28 @@ impl RespType {
         match self {
             SimpleString(data) => Some(data),
             BulkString(data) => Some(data),
+            VerbatimString(_, data) => Some(data),
             _ => None,
         }
     }
 
     pub fn into_error_bytes(self) -> Option<Vec<u8>> {
-        if let RespType::Error(error) = self {
-            Some(error)
-        } else {
-            None
+        use RespType::*;
+
+        match self {
+            Error(error) => Some(error),
+            BulkError(error) => Some(error),
+            _ => None,
         }
     }
 
     pub fn into_string(self) -> Option<String> {
-        self.into_bytes()
-            .map(|x| String::from_utf8(x).ok())
-            .flatten()
+        self.into_bytes().and_then(|x| String::from_utf8(x).ok())
     }
 
     pub fn into_error_string(self) -> Option<String> {
         self.into_error_bytes()
-            .map(|x| String::from_utf8(x).ok())
-            .flatten()
+            .and_then(|x| String::from_utf8(x).ok())
     }
 
     pub fn into_value(self) -> Result<Value, Value> {
@@ -186,11 +230,21 @@ impl RespType {
 
         match self {
             Integer(data) => Ok(Value::Int(data)),
-            Array(data) => {
+            Boolean(data) => Ok(Value::Bool(data)),
+            Double(data) => Ok(Value::Double(data)),
+            BigNumber(data) => Ok(Value::String(String::from_utf8_lossy(&data).into_owned())),
+            Array(data) | Set(data) | Push(data) => {
                 let converted: Result<Vec<Value>, Value> =
                     data.into_iter().map(|x| x.into()).collect();
                 Ok(Value::Array(converted?))
             }
+            Map(data) => {
+                let converted: Result<Vec<(Value, Value)>, Value> = data
+                    .into_iter()
+                    .map(|(key, value)| Ok((key.into_value()?, value.into_value()?)))
+                    .collect();
+                Ok(Value::Map(converted?))
+            }
             _ => unreachable!(),
         }
     }
@@ -258,3 +312,46 @@ fn resp_type_into_value_array() {
         ]))
     )
 }
+
+#[test]
+fn resp_type_into_value_resp3() {
+    let result: Result<Value, Value> = RespType::Map(vec![
+        (
+            RespType::SimpleString(b"flag".to_vec()),
+            RespType::Boolean(true),
+        ),
+        (
+            RespType::VerbatimString(*b"txt", b"text".to_vec()),
+            RespType::Set(vec![RespType::Double(1.5), RespType::Null]),
+        ),
+    ])
+    .into();
+
+    assert_eq!(
+        result,
+        Ok(Value::Map(vec![
+            (Value::String("flag".to_string()), Value::Bool(true)),
+            (
+                Value::String("text".to_string()),
+                Value::Array(vec![Value::Double(1.5), Value::Null])
+            ),
+        ]))
+    )
+}
+
+#[test]
+fn resp_type_into_value_bulk_error() {
+    let result: Result<Value, Value> = RespType::BulkError(b"SYNTAX invalid".to_vec()).into();
+
+    assert_eq!(result, Err(Value::String("SYNTAX invalid".to_string())))
+}
+
+#[test]
+fn resp_type_ref_streamed_string_to_owned() {
+    let streamed = RespTypeRef::StreamedString(vec![b"Hell", b"o wor", b"ld"]);
+
+    assert_eq!(
+        streamed.to_owned(),
+        RespType::BulkString(b"Hello world".to_vec())
+    )
+}
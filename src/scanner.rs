@@ -0,0 +1,544 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::lexer::{find_newline, Token, TokenType};
+use crate::parser::parse_size;
+use crate::{Lexer, ParseError, ParserOptions, RespErrorType};
+
+enum Scan<'a> {
+    Incomplete,
+    Invalid(ParseError<'a>),
+}
+
+impl<'a> Scan<'a> {
+    fn invalid(error_type: RespErrorType, token: Option<Token<'a>>) -> Scan<'a> {
+        Scan::Invalid(ParseError { token, error_type })
+    }
+}
+
+/// Returns the length of the first frame in `data`, or `None` when the frame is not complete
+/// yet. Only the structure is validated, nothing is allocated besides a stack of the open
+/// aggregates.
+pub fn frame_len(data: &[u8]) -> Result<Option<usize>, ParseError<'_>> {
+    frame_len_with_options(data, ParserOptions::default())
+}
+
+/// Like `frame_len`, but enforces the limits of `options`.
+pub fn frame_len_with_options(
+    data: &[u8],
+    options: ParserOptions,
+) -> Result<Option<usize>, ParseError<'_>> {
+    FrameScanner::new(options).scan(data)
+}
+
+/// Splits a buffer of pipelined frames into the ranges of its complete frames, an incomplete
+/// frame at the end is left out. Token positions in errors are relative to the failing frame.
+pub fn split_frames(
+    data: &[u8],
+    options: ParserOptions,
+) -> Result<Vec<Range<usize>>, ParseError<'_>> {
+    let mut frames = Vec::new();
+    let mut start = 0;
+    let mut scanner = FrameScanner::new(options);
+
+    while start < data.len() {
+        match scanner.scan(&data[start..]) {
+            Ok(Some(length)) => {
+                frames.push(start..start + length);
+                start += length;
+            }
+            Ok(None) => break,
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(frames)
+}
+
+// an aggregate that was opened but not closed yet
+#[derive(Debug, Clone)]
+enum Open {
+    // values left, keys and values of maps count separately
+    Sized(i64),
+    // `pending` values of the current entry are missing, `size` locates the `?` for errors
+    Streamed {
+        per_entry: i64,
+        pending: i64,
+        len: usize,
+        size: Range<usize>,
+        size_type: TokenType,
+    },
+}
+
+/// `frame_len_with_options` for a frame that arrives in pieces. The values that were complete
+/// in the previous call are not scanned again, so every call has to pass the same frame with
+/// more data appended. Once a frame was found or rejected the scanner starts over.
+#[derive(Debug, Clone)]
+pub(crate) struct FrameScanner {
+    options: ParserOptions,
+    // start of the first value that was not scanned completely
+    position: usize,
+    stack: Vec<Open>,
+    // total of the open streamed string
+    chunks: Option<usize>,
+    // end of the payload the last step waited for
+    payload_end: usize,
+    wanted: usize,
+}
+
+impl FrameScanner {
+    pub(crate) fn new(options: ParserOptions) -> FrameScanner {
+        FrameScanner {
+            options,
+            position: 0,
+            stack: Vec::new(),
+            chunks: None,
+            payload_end: 0,
+            wanted: 0,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn options(&self) -> ParserOptions {
+        self.options
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.position = 0;
+        self.stack.clear();
+        self.chunks = None;
+        self.wanted = 0;
+    }
+
+    /// Length the frame has at least, taken from the sizes of the headers scanned so far.
+    /// Scanning before this many bytes are available can not make progress.
+    #[cfg(any(feature = "std", test))]
+    pub(crate) fn wanted(&self) -> usize {
+        self.wanted
+    }
+
+    pub(crate) fn scan<'a>(&mut self, data: &'a [u8]) -> Result<Option<usize>, ParseError<'a>> {
+        let mut lexer = Lexer::resume(data, self.position);
+
+        loop {
+            self.payload_end = 0;
+
+            match self.step(data, &mut lexer) {
+                Ok(true) => {
+                    self.reset();
+                    return Ok(Some(lexer.position()));
+                }
+                Ok(false) => self.position = lexer.position(),
+                Err(Scan::Incomplete) => {
+                    let current = match self.chunks {
+                        // a chunk that is not the last one is followed by at least `;0\r\n`
+                        Some(_) if self.payload_end > 0 => self.payload_end.saturating_add(4),
+                        Some(_) => (data.len() + 1).max(self.position + 4),
+                        None => self.payload_end.max(data.len() + 1),
+                    };
+                    self.wanted = current.saturating_add(self.rest_len());
+                    return Ok(None);
+                }
+                Err(Scan::Invalid(error)) => {
+                    self.reset();
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    // the smallest number of bytes the open aggregates need after the current value
+    fn rest_len(&self) -> usize {
+        // the next token may be the `.` of the innermost aggregate
+        let innermost = self.stack.len().wrapping_sub(1);
+        let closing = self
+            .stack
+            .iter()
+            .enumerate()
+            .map(|(index, open)| match open {
+                // `_\r\n` is the shortest value
+                Open::Sized(remaining) => usize::try_from(remaining - 1)
+                    .unwrap_or(usize::MAX)
+                    .saturating_mul(3),
+                Open::Streamed { pending: 0, .. }
+                    if index == innermost && self.chunks.is_none() =>
+                {
+                    0
+                }
+                Open::Streamed { .. } => 3,
+            });
+
+        closing.fold(0, usize::saturating_add)
+    }
+
+    // scans one value, the header of an aggregate or one chunk of a streamed string, the
+    // state only changes once the step is complete. Returns whether the frame is complete.
+    fn step<'a>(&mut self, data: &'a [u8], lexer: &mut Lexer<'a>) -> Result<bool, Scan<'a>> {
+        if let Some(total) = self.chunks {
+            return self.skip_chunk(data, lexer, total);
+        }
+
+        let token = next_token(lexer)?;
+
+        match token.tokentype {
+            TokenType::SimpleStringStart
+            | TokenType::ErrorStart
+            | TokenType::IntegerStart
+            | TokenType::BooleanStart
+            | TokenType::DoubleStart
+            | TokenType::BigNumberStart => {
+                if next_token(lexer)?.tokentype != TokenType::Newline {
+                    expect_newline(lexer)?;
+                }
+            }
+            TokenType::NullStart => expect_newline(lexer)?,
+            TokenType::StreamEnd => {
+                expect_newline(lexer)?;
+                match self.stack.last() {
+                    Some(Open::Streamed { pending: 0, .. }) => {
+                        self.stack.pop();
+                    }
+                    _ => return Err(Scan::invalid(RespErrorType::InvalidStart, Some(token))),
+                }
+            }
+            TokenType::BulkStringStart => match self.skip_size(lexer, TokenType::BulkStringSize)? {
+                (Some(size), _) => self.skip_payload(lexer, size)?,
+                (None, _) => {
+                    self.chunks = Some(0);
+                    return Ok(false);
+                }
+            },
+            TokenType::BulkErrorStart => self.skip_blob(lexer, TokenType::BulkErrorSize)?,
+            TokenType::VerbatimStringStart => {
+                self.skip_blob(lexer, TokenType::VerbatimStringSize)?
+            }
+            TokenType::ArrayStart => return self.open(data, lexer, token, TokenType::ArraySize, 1),
+            TokenType::SetStart => return self.open(data, lexer, token, TokenType::SetSize, 1),
+            TokenType::PushStart => return self.open(data, lexer, token, TokenType::PushSize, 1),
+            TokenType::MapStart => return self.open(data, lexer, token, TokenType::MapSize, 2),
+            _ => return Err(Scan::invalid(RespErrorType::InvalidStart, Some(token))),
+        }
+
+        self.close_value(data)
+    }
+
+    fn open<'a>(
+        &mut self,
+        data: &'a [u8],
+        lexer: &mut Lexer<'a>,
+        start: Token<'a>,
+        size_type: TokenType,
+        per_entry: i64,
+    ) -> Result<bool, Scan<'a>> {
+        if self
+            .options
+            .max_depth
+            .is_some_and(|max| self.stack.len() >= max)
+        {
+            return Err(Scan::invalid(RespErrorType::LimitExceeded, Some(start)));
+        }
+
+        match self.skip_size(lexer, size_type)? {
+            (Some(size), token) => {
+                let Some(values) = size.checked_mul(per_entry) else {
+                    return Err(Scan::invalid(RespErrorType::InvalidSize, Some(token)));
+                };
+                if values <= 0 {
+                    return self.close_value(data);
+                }
+                self.stack.push(Open::Sized(values));
+            }
+            (None, token) if size_type == TokenType::PushSize => {
+                return Err(Scan::invalid(RespErrorType::InvalidSize, Some(token)));
+            }
+            (None, token) => self.stack.push(Open::Streamed {
+                per_entry,
+                pending: 0,
+                len: 0,
+                size: token.start..token.end,
+                size_type,
+            }),
+        }
+
+        Ok(false)
+    }
+
+    // counts a complete value in the aggregate that contains it, returns whether it was the
+    // root
+    fn close_value<'a>(&mut self, data: &'a [u8]) -> Result<bool, Scan<'a>> {
+        loop {
+            match self.stack.last_mut() {
+                None => return Ok(true),
+                Some(Open::Sized(remaining)) => {
+                    *remaining -= 1;
+                    if *remaining > 0 {
+                        return Ok(false);
+                    }
+                    self.stack.pop();
+                }
+                Some(Open::Streamed {
+                    per_entry,
+                    pending,
+                    len,
+                    size,
+                    size_type,
+                }) => {
+                    if *pending > 0 {
+                        *pending -= 1;
+                        return Ok(false);
+                    }
+
+                    *len += 1;
+                    if self.options.max_aggregate_len.is_some_and(|max| *len > max) {
+                        let token = Token {
+                            start: size.start,
+                            end: size.end,
+                            data: &data[size.clone()],
+                            tokentype: *size_type,
+                        };
+                        return Err(Scan::invalid(RespErrorType::LimitExceeded, Some(token)));
+                    }
+                    *pending = *per_entry - 1;
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    fn skip_chunk<'a>(
+        &mut self,
+        data: &'a [u8],
+        lexer: &mut Lexer<'a>,
+        total: usize,
+    ) -> Result<bool, Scan<'a>> {
+        let token = next_token(lexer)?;
+        if token.tokentype != TokenType::ChunkStart {
+            return Err(Scan::invalid(RespErrorType::InvalidData, Some(token)));
+        }
+
+        match self.skip_size(lexer, TokenType::ChunkSize)? {
+            (Some(0), _) => {
+                self.chunks = None;
+                self.close_value(data)
+            }
+            (Some(size), token) if size > 0 => {
+                let total = total.saturating_add(size as usize);
+                if self.options.max_bulk_len.is_some_and(|max| total > max) {
+                    return Err(Scan::invalid(RespErrorType::LimitExceeded, Some(token)));
+                }
+                self.skip_payload(lexer, size)?;
+                self.chunks = Some(total);
+                Ok(false)
+            }
+            (_, token) => Err(Scan::invalid(RespErrorType::InvalidSize, Some(token))),
+        }
+    }
+
+    fn skip_blob<'a>(
+        &mut self,
+        lexer: &mut Lexer<'a>,
+        size_type: TokenType,
+    ) -> Result<(), Scan<'a>> {
+        match self.skip_size(lexer, size_type)? {
+            (Some(size), _) if size >= 0 => self.skip_payload(lexer, size),
+            (_, token) => Err(Scan::invalid(RespErrorType::InvalidSize, Some(token))),
+        }
+    }
+
+    fn skip_payload<'a>(&mut self, lexer: &mut Lexer<'a>, size: i64) -> Result<(), Scan<'a>> {
+        if size > 0 {
+            self.payload_end = usize::try_from(size)
+                .unwrap_or(usize::MAX)
+                .saturating_add(Lexer::position(lexer) + 2);
+
+            let payload = next_token(lexer)?;
+            if payload.data.len() < size as usize {
+                return Err(Scan::Incomplete);
+            }
+        }
+
+        if size >= 0 {
+            expect_newline(lexer)?;
+        }
+
+        Ok(())
+    }
+
+    // returns `None` for the `?` size of streamed types, together with the size token for errors
+    fn skip_size<'a>(
+        &self,
+        lexer: &mut Lexer<'a>,
+        token_type: TokenType,
+    ) -> Result<(Option<i64>, Token<'a>), Scan<'a>> {
+        let token = next_token(lexer)?;
+        if token.tokentype != token_type {
+            return Err(Scan::invalid(RespErrorType::InvalidData, Some(token)));
+        }
+        expect_newline(lexer)?;
+
+        if token.data == b"?" {
+            return Ok((None, token));
+        }
+
+        match parse_size(token.clone(), &self.options) {
+            Ok(size) => Ok((Some(size), token)),
+            Err(error) => Err(Scan::Invalid(error)),
+        }
+    }
+}
+
+fn expect_newline<'a>(lexer: &mut Lexer<'a>) -> Result<(), Scan<'a>> {
+    let token = next_token(lexer)?;
+    match token.tokentype {
+        TokenType::Newline => Ok(()),
+        _ => Err(Scan::invalid(RespErrorType::NewLineMissing, Some(token))),
+    }
+}
+
+fn next_token<'a>(lexer: &mut Lexer<'a>) -> Result<Token<'a>, Scan<'a>> {
+    match lexer.next() {
+        Some(token) => Ok(token),
+        None if find_newline(lexer.remaining()).is_some() => {
+            Err(Scan::invalid(RespErrorType::InvalidData, None))
+        }
+        None => Err(Scan::Incomplete),
+    }
+}
+
+#[test]
+fn frame_len_complete_and_incomplete() {
+    let data = b"*2\r\n$5\r\nhello\r\n%?\r\n+a\r\n:1\r\n.\r\n+OK\r\n";
+
+    assert_eq!(frame_len(data).unwrap(), Some(data.len() - 5));
+    for end in 0..data.len() - 5 {
+        assert_eq!(frame_len(&data[..end]).unwrap(), None);
+    }
+    assert_eq!(frame_len(b"$3\r\nfoo\r\n").unwrap(), Some(9));
+    assert_eq!(
+        frame_len(b"*1\r\n.\r\n").unwrap_err().error_type(),
+        RespErrorType::InvalidStart
+    );
+}
+
+#[test]
+fn frame_len_limits() {
+    let options = ParserOptions {
+        max_depth: Some(2),
+        max_bulk_len: Some(4),
+        max_aggregate_len: Some(2),
+        ..Default::default()
+    };
+    let table: [(&[u8], bool); 8] = [
+        (b"*1\r\n*1\r\n:1\r\n", true),
+        (b"*1\r\n*1\r\n*0\r\n", false),
+        (b"$4\r\ntext\r\n", true),
+        (b"$5\r\ntexts\r\n", false),
+        (b"$?\r\n;3\r\nabc\r\n;2\r\nde\r\n;0\r\n", false),
+        (b"*3\r\n:1\r\n:2\r\n:3\r\n", false),
+        (b"~?\r\n:1\r\n:2\r\n:3\r\n.\r\n", false),
+        (b"%2\r\n:1\r\n:2\r\n:3\r\n:4\r\n", true),
+    ];
+
+    for (input, ok) in table {
+        let result = frame_len_with_options(input, options);
+        let parsed = crate::Parser::new_with_options(Lexer::new(input), options).parse();
+
+        match ok {
+            true => {
+                assert_eq!(result.unwrap(), Some(input.len()));
+                assert!(parsed.is_ok());
+            }
+            false => {
+                assert_eq!(
+                    result.unwrap_err().error_type(),
+                    RespErrorType::LimitExceeded
+                );
+                assert_eq!(
+                    parsed.unwrap_err().error_type(),
+                    RespErrorType::LimitExceeded
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn split_frames_leaves_incomplete_tail() {
+    let data = b"+OK\r\n:1\r\n*2\r\n:1\r\n";
+
+    assert_eq!(
+        split_frames(data, ParserOptions::default()).unwrap(),
+        vec![0..5, 5..9]
+    );
+}
+
+#[test]
+fn frame_len_huge_sizes() {
+    // must not overflow, each header is either waiting for more data or rejected
+    for data in [
+        &b"*9223372036854775807\r\n"[..],
+        b"%9223372036854775807\r\n",
+        b"%4611686018427387904\r\n",
+        b"|9223372036854775807\r\n",
+        b"~9223372036854775807\r\n",
+    ] {
+        assert!(!matches!(frame_len(data), Ok(Some(_))));
+    }
+    assert_eq!(
+        frame_len(b"%9223372036854775807\r\n")
+            .unwrap_err()
+            .error_type(),
+        RespErrorType::InvalidSize
+    );
+}
+
+#[test]
+fn frame_len_agrees_with_parser() {
+    use crate::{ParseMode, Parser};
+
+    let inputs: [&[u8]; 8] = [
+        b"~-1\r\n",
+        b"%-1\r\n",
+        b">-1\r\n",
+        b">?\r\n",
+        b"!-1\r\n",
+        b"*-1\r\n",
+        b"$01\r\na\r\n",
+        b"*+1\r\n:1\r\n",
+    ];
+
+    for data in inputs {
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            let options = ParserOptions {
+                mode,
+                ..Default::default()
+            };
+            let scanned = frame_len_with_options(data, options).map_err(|x| x.error_type());
+            let parsed = Parser::new_with_options(Lexer::new(data), options)
+                .parse()
+                .map_err(|x| x.error_type());
+
+            assert_eq!(scanned.err(), parsed.err(), "{:?}", data);
+        }
+    }
+}
+
+#[test]
+fn frame_scanner_resumes() {
+    let data = b"*3\r\n$10\r\n0123456789\r\n%?\r\n+a\r\n:1\r\n.\r\n$?\r\n;2\r\nab\r\n;0\r\n+OK\r\n";
+    let mut scanner = FrameScanner::new(ParserOptions::default());
+
+    for end in 0..data.len() - 5 {
+        assert_eq!(scanner.scan(&data[..end]).unwrap(), None);
+        assert!(scanner.wanted() > end);
+        assert!(scanner.wanted() <= data.len() - 5);
+    }
+    assert_eq!(scanner.scan(data).unwrap(), Some(data.len() - 5));
+
+    // the declared sizes tell how much is missing at least
+    assert_eq!(scanner.scan(b"$10\r\nab").unwrap(), None);
+    assert_eq!(scanner.wanted(), 17);
+    scanner.reset();
+    assert_eq!(scanner.scan(b"*3\r\n:1\r\n").unwrap(), None);
+    assert_eq!(scanner.wanted(), 12);
+}
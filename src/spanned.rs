@@ -0,0 +1,66 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::RespTypeRef;
+
+/// A parsed node together with its byte ranges in the input. `frame` covers the whole
+/// encoded value, `header` the type byte and size line and `payload` the content between
+/// the header and the trailing newline or terminator.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Spanned<'a> {
+    pub frame: Range<usize>,
+    pub header: Range<usize>,
+    pub payload: Range<usize>,
+    pub node: SpannedNode<'a>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SpannedNode<'a> {
+    Value(RespTypeRef<'a>),
+    Array(Vec<Spanned<'a>>),
+    Map(Vec<(Spanned<'a>, Spanned<'a>)>),
+    Set(Vec<Spanned<'a>>),
+    Push(Vec<Spanned<'a>>),
+}
+
+impl<'a> Spanned<'a> {
+    pub fn children(&self) -> Vec<&Spanned<'a>> {
+        match &self.node {
+            SpannedNode::Value(_) => Vec::new(),
+            SpannedNode::Array(items) | SpannedNode::Set(items) | SpannedNode::Push(items) => {
+                items.iter().collect()
+            }
+            SpannedNode::Map(pairs) => pairs.iter().flat_map(|(key, value)| [key, value]).collect(),
+        }
+    }
+
+    pub fn into_resp_type_ref(self) -> RespTypeRef<'a> {
+        fn convert<'a>(items: Vec<Spanned<'a>>) -> Vec<RespTypeRef<'a>> {
+            items.into_iter().map(|x| x.into_resp_type_ref()).collect()
+        }
+
+        match self.node {
+            SpannedNode::Value(item) => item,
+            SpannedNode::Array(items) => RespTypeRef::Array(convert(items)),
+            SpannedNode::Set(items) => RespTypeRef::Set(convert(items)),
+            SpannedNode::Push(items) => RespTypeRef::Push(convert(items)),
+            SpannedNode::Map(pairs) => RespTypeRef::Map(
+                pairs
+                    .into_iter()
+                    .map(|(key, value)| (key.into_resp_type_ref(), value.into_resp_type_ref()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[test]
+fn spanned_children() {
+    let data = b"%1\r\n+a\r\n:1\r\n";
+    let spanned = crate::Parser::new_from_bytes(data).parse_spanned().unwrap();
+    let children = spanned.children();
+
+    assert_eq!(children.len(), 2);
+    assert_eq!(&data[children[0].frame.clone()], b"+a\r\n");
+    assert_eq!(&data[children[1].frame.clone()], b":1\r\n");
+}
@@ -0,0 +1,270 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::{ParseError, Parser, RespTypeRef};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NodeKind {
+    SimpleString,
+    Error,
+    Integer(i64),
+    BulkString,
+    NullString,
+    Array,
+    NullArray,
+    Null,
+    Boolean(bool),
+    Double(f64),
+    BigNumber,
+    BulkError,
+    VerbatimString([u8; 3]),
+    Map,
+    Set,
+    Push,
+    StreamedString,
+    // one part of a `StreamedString`
+    Chunk,
+}
+
+/// A single entry of a `Tape`. `payload` points into the input, `len` counts the direct
+/// children (keys and values for maps, chunks for streamed strings) and `next` is the index
+/// of the first node after this subtree.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub payload: Range<usize>,
+    pub len: usize,
+    pub next: usize,
+}
+
+/// All nodes of a frame in depth-first order in one vector, the children of a node directly
+/// follow it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Tape<'a> {
+    input: &'a [u8],
+    nodes: Vec<Node>,
+}
+
+impl<'a> Tape<'a> {
+    pub fn parse(input: &'a [u8]) -> Result<Tape<'a>, ParseError<'a>> {
+        Parser::new_from_bytes(input).parse_tape()
+    }
+
+    pub(crate) fn new(input: &'a [u8], nodes: Vec<Node>) -> Tape<'a> {
+        Tape { input, nodes }
+    }
+
+    pub fn input(&self) -> &'a [u8] {
+        self.input
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn root(&self) -> Cursor<'_, 'a> {
+        Cursor {
+            tape: self,
+            index: 0,
+        }
+    }
+
+    pub fn to_resp_type_ref(&self) -> RespTypeRef<'a> {
+        self.root().to_resp_type_ref()
+    }
+
+    /// Returns the node buffer so it can be passed to `Parser::parse_tape_with`.
+    pub fn into_nodes(self) -> Vec<Node> {
+        self.nodes
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cursor<'t, 'a> {
+    tape: &'t Tape<'a>,
+    index: usize,
+}
+
+impl<'t, 'a> Cursor<'t, 'a> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn node(&self) -> &'t Node {
+        &self.tape.nodes[self.index]
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.node().kind
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &self.tape.input[self.node().payload.clone()]
+    }
+
+    /// Number of direct children, for maps this counts keys and values separately.
+    pub fn len(&self) -> usize {
+        self.node().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(
+            self.kind(),
+            NodeKind::NullString | NodeKind::NullArray | NodeKind::Null
+        )
+    }
+
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self.kind() {
+            NodeKind::SimpleString
+            | NodeKind::BulkString
+            | NodeKind::VerbatimString(_)
+            | NodeKind::Chunk => Some(self.payload()),
+            _ => None,
+        }
+    }
+
+    pub fn as_error_bytes(&self) -> Option<&'a [u8]> {
+        match self.kind() {
+            NodeKind::Error | NodeKind::BulkError => Some(self.payload()),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self.kind() {
+            NodeKind::Integer(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn children(&self) -> Children<'t, 'a> {
+        Children {
+            tape: self.tape,
+            index: self.index + 1,
+            remaining: self.len(),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<Cursor<'t, 'a>> {
+        self.children().nth(index)
+    }
+
+    pub fn to_resp_type_ref(&self) -> RespTypeRef<'a> {
+        fn convert<'a>(cursor: &Cursor<'_, 'a>) -> Vec<RespTypeRef<'a>> {
+            cursor.children().map(|x| x.to_resp_type_ref()).collect()
+        }
+
+        let payload = self.payload();
+        match self.kind() {
+            NodeKind::SimpleString => RespTypeRef::SimpleString(payload),
+            NodeKind::Error => RespTypeRef::Error(payload),
+            NodeKind::Integer(x) => RespTypeRef::Integer(x),
+            NodeKind::BulkString | NodeKind::Chunk => RespTypeRef::BulkString(payload),
+            NodeKind::NullString => RespTypeRef::NullString,
+            NodeKind::Array => RespTypeRef::Array(convert(self)),
+            NodeKind::NullArray => RespTypeRef::NullArray,
+            NodeKind::Null => RespTypeRef::Null,
+            NodeKind::Boolean(x) => RespTypeRef::Boolean(x),
+            NodeKind::Double(x) => RespTypeRef::Double(x),
+            NodeKind::BigNumber => RespTypeRef::BigNumber(payload),
+            NodeKind::BulkError => RespTypeRef::BulkError(payload),
+            NodeKind::VerbatimString(format) => RespTypeRef::VerbatimString(format, payload),
+            NodeKind::Map => {
+                let mut children = self.children();
+                let mut pairs = Vec::with_capacity(self.len() / 2);
+                while let (Some(key), Some(value)) = (children.next(), children.next()) {
+                    pairs.push((key.to_resp_type_ref(), value.to_resp_type_ref()));
+                }
+                RespTypeRef::Map(pairs)
+            }
+            NodeKind::Set => RespTypeRef::Set(convert(self)),
+            NodeKind::Push => RespTypeRef::Push(convert(self)),
+            NodeKind::StreamedString => {
+                RespTypeRef::StreamedString(self.children().map(|x| x.payload()).collect())
+            }
+        }
+    }
+}
+
+/// Iterates the direct children of a node by following the `next` index of each sibling.
+#[derive(Debug, Clone)]
+pub struct Children<'t, 'a> {
+    tape: &'t Tape<'a>,
+    index: usize,
+    remaining: usize,
+}
+
+impl<'t, 'a> Iterator for Children<'t, 'a> {
+    type Item = Cursor<'t, 'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let cursor = Cursor {
+            tape: self.tape,
+            index: self.index,
+        };
+        self.index = self.tape.nodes[self.index].next;
+        self.remaining -= 1;
+
+        Some(cursor)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Children<'_, '_> {}
+
+#[test]
+fn tape_navigation() {
+    let data = b"*3\r\n$3\r\nfoo\r\n*2\r\n:1\r\n_\r\n$-1\r\n";
+    let tape = Tape::parse(data).unwrap();
+    let root = tape.root();
+
+    assert_eq!(root.kind(), NodeKind::Array);
+    assert_eq!(tape.nodes().len(), 6);
+    assert_eq!(root.get(0).unwrap().as_bytes(), Some(&b"foo"[..]));
+    assert_eq!(root.get(1).unwrap().get(0).unwrap().as_integer(), Some(1));
+    assert!(root.get(1).unwrap().get(1).unwrap().is_null());
+    assert!(root.get(2).unwrap().is_null());
+    assert!(root.get(3).is_none());
+}
+
+#[test]
+fn tape_matches_parse() {
+    let inputs: [&[u8]; 6] = [
+        b"%2\r\n+a\r\n:1\r\n+b\r\n~1\r\n#t\r\n",
+        b"*?\r\n:1\r\n*?\r\n.\r\n.\r\n",
+        b"%?\r\n+a\r\n,1.5\r\n.\r\n",
+        b"$?\r\n;2\r\nab\r\n;1\r\nc\r\n;0\r\n",
+        b"=8\r\ntxt:text\r\n",
+        b"*-1\r\n",
+    ];
+
+    for data in inputs {
+        let tape = Tape::parse(data).unwrap();
+        let expected = Parser::new_from_bytes(data).parse().unwrap();
+        assert_eq!(tape.to_resp_type_ref(), expected);
+    }
+}
+
+#[test]
+fn tape_huge_sizes() {
+    // the sizes come from the peer and must neither overflow nor allocate up front
+    for data in [
+        &b"*9223372036854775807\r\n"[..],
+        b"%9223372036854775807\r\n",
+        b"*100000000\r\n:1\r\n",
+    ] {
+        assert!(Tape::parse(data).is_err());
+    }
+}
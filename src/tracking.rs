@@ -0,0 +1,299 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::{PubSubError, RespType};
+
+/// Pub/Sub channel invalidations are sent to on RESP2 connections (`CLIENT TRACKING on
+/// REDIRECT <id>`).
+pub const INVALIDATE_CHANNEL: &[u8] = b"__redis__:invalidate";
+
+/// Keys a server with `CLIENT TRACKING` enabled says are no longer valid. `FlushAll` is sent
+/// after `FLUSHALL` or `FLUSHDB`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Invalidation {
+    Keys(Vec<Vec<u8>>),
+    FlushAll,
+}
+
+impl Invalidation {
+    fn from_keys(keys: RespType) -> Result<Invalidation, PubSubError> {
+        match keys {
+            RespType::Array(keys) | RespType::Set(keys) => keys
+                .into_iter()
+                .map(|key| key.into_bytes().ok_or(PubSubError::Malformed))
+                .collect::<Result<_, _>>()
+                .map(Invalidation::Keys),
+            keys if keys.is_null() => Ok(Invalidation::FlushAll),
+            _ => Err(PubSubError::Malformed),
+        }
+    }
+}
+
+/// Accepts the RESP3 push `["invalidate", keys]` and the RESP2 message
+/// `["message", "__redis__:invalidate", keys]`. Other frames give `PubSubError::NotPubSub` so
+/// they can be handled elsewhere.
+impl TryFrom<RespType> for Invalidation {
+    type Error = PubSubError;
+
+    fn try_from(frame: RespType) -> Result<Self, Self::Error> {
+        let items = match frame {
+            RespType::Push(items) | RespType::Array(items) => items,
+            _ => return Err(PubSubError::NotPubSub),
+        };
+
+        match items.first().and_then(|kind| kind.as_bytes()) {
+            Some(b"invalidate") => {
+                let [_, keys] =
+                    <[RespType; 2]>::try_from(items).map_err(|_| PubSubError::Malformed)?;
+                Invalidation::from_keys(keys)
+            }
+            Some(b"message")
+                if items.get(1).and_then(|x| x.as_bytes()) == Some(INVALIDATE_CHANNEL) =>
+            {
+                let [_, _, keys] =
+                    <[RespType; 3]>::try_from(items).map_err(|_| PubSubError::Malformed)?;
+                Invalidation::from_keys(keys)
+            }
+            _ => Err(PubSubError::NotPubSub),
+        }
+    }
+}
+
+/// Replies cached by key, kept valid by applying the invalidations of the connection that
+/// read them. With `max_entries` the oldest entry is evicted to make room.
+///
+/// An invalidation can arrive while the reply to a read is still on its way, so a plain
+/// `insert` afterwards would cache a stale value. Call `reserve` before sending the read and
+/// `complete` with its reply instead, invalidations in between make `complete` drop it. A read
+/// that fails or is abandoned has to be ended with `cancel`.
+/// Invalidations sent while the connection was down are lost, call `clear` on reconnect.
+#[derive(Debug, Clone, Default)]
+pub struct LocalCache {
+    entries: BTreeMap<Vec<u8>, (u64, RespType)>,
+    // insertion order of `entries`, the first one is evicted
+    order: BTreeMap<u64, Vec<u8>>,
+    pending: BTreeMap<Vec<u8>, u64>,
+    next_id: u64,
+    max_entries: Option<usize>,
+}
+
+impl LocalCache {
+    pub fn new(max_entries: Option<usize>) -> LocalCache {
+        LocalCache {
+            max_entries,
+            ..LocalCache::default()
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&RespType> {
+        self.entries.get(key).map(|(_, value)| value)
+    }
+
+    pub fn insert(&mut self, key: impl Into<Vec<u8>>, value: RespType) {
+        let key = key.into();
+        self.pending.remove(&key);
+        self.store(key, value);
+    }
+
+    /// Marks `key` as being read, the returned id is passed to `complete` with the reply or to
+    /// `cancel` if there is none.
+    pub fn reserve(&mut self, key: impl Into<Vec<u8>>) -> u64 {
+        let id = self.next_id();
+        self.pending.insert(key.into(), id);
+        id
+    }
+
+    /// Caches the reply of a read started with `reserve`. Returns false and drops the value if
+    /// the key was invalidated or reserved again since.
+    pub fn complete(&mut self, key: &[u8], id: u64, value: RespType) -> bool {
+        if self.pending.get(key) != Some(&id) {
+            return false;
+        }
+
+        self.pending.remove(key);
+        self.store(key.to_vec(), value);
+        true
+    }
+
+    /// Ends a read started with `reserve` that got no reply.
+    pub fn cancel(&mut self, key: &[u8], id: u64) {
+        if self.pending.get(key) == Some(&id) {
+            self.pending.remove(key);
+        }
+    }
+
+    /// Reads started with `reserve` that were not completed or cancelled yet.
+    pub fn pending_reads(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<RespType> {
+        self.pending.remove(key);
+        let (id, value) = self.entries.remove(key)?;
+        self.order.remove(&id);
+        Some(value)
+    }
+
+    pub fn apply(&mut self, invalidation: &Invalidation) {
+        match invalidation {
+            Invalidation::Keys(keys) => {
+                for key in keys {
+                    self.remove(key);
+                }
+            }
+            Invalidation::FlushAll => self.clear(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drops all entries and pending reads.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.pending.clear();
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn store(&mut self, key: Vec<u8>, value: RespType) {
+        if self.max_entries == Some(0) {
+            return;
+        }
+
+        if let Some((id, _)) = self.entries.get(&key) {
+            self.order.remove(id);
+        } else if self
+            .max_entries
+            .is_some_and(|max| self.entries.len() >= max)
+        {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        let id = self.next_id();
+        self.order.insert(id, key.clone());
+        self.entries.insert(key, (id, value));
+    }
+}
+
+#[test]
+fn tracking_invalidation_forms() {
+    let decode = |data: &[u8]| Invalidation::try_from(crate::bytes_to_resp_type(data).unwrap());
+
+    assert_eq!(
+        decode(b">2\r\n$10\r\ninvalidate\r\n*2\r\n$1\r\na\r\n$1\r\nb\r\n"),
+        Ok(Invalidation::Keys(vec![b"a".to_vec(), b"b".to_vec()]))
+    );
+    assert_eq!(
+        decode(b">2\r\n$10\r\ninvalidate\r\n_\r\n"),
+        Ok(Invalidation::FlushAll)
+    );
+    assert_eq!(
+        decode(b"*3\r\n$7\r\nmessage\r\n$20\r\n__redis__:invalidate\r\n*1\r\n$3\r\nkey\r\n"),
+        Ok(Invalidation::Keys(vec![b"key".to_vec()]))
+    );
+    assert_eq!(
+        decode(b"*3\r\n$7\r\nmessage\r\n$20\r\n__redis__:invalidate\r\n*-1\r\n"),
+        Ok(Invalidation::FlushAll)
+    );
+
+    assert_eq!(
+        decode(b"*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n"),
+        Err(PubSubError::NotPubSub)
+    );
+    assert_eq!(
+        decode(b">2\r\n$10\r\ninvalidate\r\n:1\r\n"),
+        Err(PubSubError::Malformed)
+    );
+    assert_eq!(
+        decode(b">3\r\n$10\r\ninvalidate\r\n*0\r\n*0\r\n"),
+        Err(PubSubError::Malformed)
+    );
+}
+
+#[test]
+fn tracking_local_cache() {
+    let value = |x: &[u8]| RespType::BulkString(x.to_vec());
+    let mut cache = LocalCache::new(Some(2));
+
+    cache.insert("c", value(b"1"));
+    cache.insert("b", value(b"2"));
+    cache.insert("a", value(b"3"));
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get(b"c"), None);
+
+    // replacing a value makes it the newest entry
+    cache.insert("b", value(b"2"));
+    cache.insert("c", value(b"3"));
+    assert_eq!(cache.get(b"a"), None);
+
+    cache.apply(&Invalidation::Keys(vec![
+        b"b".to_vec(),
+        b"missing".to_vec(),
+    ]));
+    assert_eq!(cache.get(b"b"), None);
+    assert_eq!(cache.get(b"c"), Some(&value(b"3")));
+
+    cache.apply(&Invalidation::FlushAll);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn tracking_local_cache_pending_reads() {
+    let value = |x: &[u8]| RespType::BulkString(x.to_vec());
+    let mut cache = LocalCache::new(None);
+
+    let id = cache.reserve("a");
+    assert!(cache.complete(b"a", id, value(b"1")));
+    assert_eq!(cache.get(b"a"), Some(&value(b"1")));
+    assert!(!cache.complete(b"a", id, value(b"2")));
+
+    // the invalidation overtakes the reply
+    let id = cache.reserve("b");
+    cache.apply(&Invalidation::Keys(vec![b"b".to_vec()]));
+    assert!(!cache.complete(b"b", id, value(b"1")));
+    assert_eq!(cache.get(b"b"), None);
+
+    let old = cache.reserve("c");
+    let new = cache.reserve("c");
+    assert!(!cache.complete(b"c", old, value(b"1")));
+    assert!(cache.complete(b"c", new, value(b"2")));
+
+    let id = cache.reserve("d");
+    cache.apply(&Invalidation::FlushAll);
+    assert!(!cache.complete(b"d", id, value(b"1")));
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn tracking_local_cache_cancelled_reads() {
+    let value = |x: &[u8]| RespType::BulkString(x.to_vec());
+    let mut cache = LocalCache::new(Some(1));
+
+    let old = cache.reserve("a");
+    let new = cache.reserve("a");
+    // only the latest read of a key can end it
+    cache.cancel(b"a", old);
+    assert_eq!(cache.pending_reads(), 1);
+    cache.cancel(b"a", new);
+    assert_eq!(cache.pending_reads(), 0);
+    assert!(!cache.complete(b"a", new, value(b"1")));
+
+    for key in ["a", "b", "c"] {
+        let id = cache.reserve(key);
+        cache.cancel(key.as_bytes(), id);
+    }
+    assert_eq!(cache.pending_reads(), 0);
+    assert!(cache.is_empty());
+}
@@ -1,3 +1,6 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
 use crate::RespType;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -5,26 +8,36 @@ pub enum Value {
     Bytes(Vec<u8>),
     String(String),
     Int(i64),
+    Bool(bool),
+    Double(f64),
     Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
     Null,
 }
 
-impl Into<RespType> for Value {
-    fn into(self) -> RespType {
+impl From<Value> for RespType {
+    fn from(val: Value) -> Self {
         use Value::*;
 
-        match self {
+        match val {
             Bytes(data) => RespType::BulkString(data),
             String(data) => RespType::BulkString(data.into()),
             Int(data) => RespType::Integer(data),
+            Bool(data) => RespType::Boolean(data),
+            Double(data) => RespType::Double(data),
             Array(data) => RespType::Array(data.into_iter().map(|x| x.into()).collect()),
+            Map(data) => RespType::Map(
+                data.into_iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect(),
+            ),
             Null => RespType::NullString,
         }
     }
 }
 
-impl Into<Result<Value, Value>> for RespType {
-    fn into(self) -> Result<Value, Value> {
-        self.into_value()
+impl From<RespType> for Result<Value, Value> {
+    fn from(val: RespType) -> Self {
+        val.into_value()
     }
 }
@@ -0,0 +1,116 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use redis_resp::{Decoder, Lexer, Parser, RespType};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|x| x.set(x.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|x| x.set(x.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// counts the allocations of the current thread, other tests run on their own threads
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(|x| x.get());
+    f();
+    ALLOCATIONS.with(|x| x.get()) - before
+}
+
+fn replies(count: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in 0..count {
+        data.extend_from_slice(b"+OK\r\n");
+        data.extend_from_slice(format!(":{}\r\n", i).as_bytes());
+        data.extend_from_slice(b"*3\r\n$3\r\nfoo\r\n$-1\r\n%1\r\n+key\r\n$5\r\nvalue\r\n");
+    }
+    data
+}
+
+#[test]
+fn decoder_recycled_frames_do_not_allocate() {
+    let data = replies(1000);
+    let mut decoder = Decoder::new(&data[..]);
+
+    // warm up the pool, the node buffer and the read buffer, which grows once when a read
+    // has to keep a partial frame
+    for _ in 0..1000 {
+        let item = decoder.decode().unwrap().unwrap();
+        decoder.recycle(item);
+    }
+
+    let allocations = count_allocations(|| {
+        while let Some(item) = decoder.decode().unwrap() {
+            decoder.recycle(item);
+        }
+    });
+
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn decoder_reset_keeps_buffers() {
+    let first = replies(10);
+    let second = replies(10);
+    let mut decoder = Decoder::new(&first[..]);
+
+    while let Some(item) = decoder.decode().unwrap() {
+        decoder.recycle(item);
+    }
+
+    let allocations = count_allocations(|| {
+        decoder.reset(&second[..]);
+        while let Some(item) = decoder.decode().unwrap() {
+            decoder.recycle(item);
+        }
+    });
+
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn parser_reset_and_scalar_frames_do_not_allocate() {
+    let frames: [&[u8]; 4] = [b"+OK\r\n", b":12\r\n", b"$5\r\nhello\r\n", b"_\r\n"];
+    let mut parser = Parser::new(Lexer::new(frames[0]));
+
+    let allocations = count_allocations(|| {
+        for frame in frames {
+            parser.reset(frame);
+            assert!(parser.parse().is_ok());
+        }
+    });
+
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn decoder_without_recycling_allocates_per_frame() {
+    let data = replies(100);
+    let mut decoder = Decoder::new(&data[..]);
+    decoder.decode().unwrap();
+
+    let allocations = count_allocations(|| {
+        assert_eq!(decoder.decode().unwrap(), Some(RespType::Integer(0)));
+        decoder.decode().unwrap();
+    });
+
+    assert!(allocations > 0);
+}
@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+const VALID: &[u8] = b"*2\r\n$6\r\nSELECT\r\n$1\r\n0\r\n*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n\
+    *3\r\n$3\r\nset\r\n$1\r\nb\r\n$1\r\n2\r\n";
+
+fn write_aof(name: &str, data: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("check_aof_{}_{}.aof", std::process::id(), name));
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+fn check_aof(args: &[&str], path: &PathBuf) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_check-aof"))
+        .args(args)
+        .arg(path)
+        .output()
+        .unwrap();
+
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn check_aof_valid_file() {
+    let path = write_aof("valid", VALID);
+    let (success, output) = check_aof(&[], &path);
+
+    assert!(success);
+    assert!(output.contains("AOF is valid"));
+    let set = output.lines().find(|x| x.starts_with("SET")).unwrap();
+    assert_eq!(
+        set.split_whitespace().collect::<Vec<_>>(),
+        ["SET", "2", "54"]
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn check_aof_fixes_truncated_file() {
+    let mut data = VALID.to_vec();
+    data.extend_from_slice(b"*2\r\n$4\r\nINCR\r\n$1");
+    let path = write_aof("truncated", &data);
+
+    let (success, output) = check_aof(&[], &path);
+    assert!(!success);
+    assert!(output.contains(&format!("ends at offset {}", VALID.len())));
+    assert_eq!(std::fs::read(&path).unwrap(), data);
+
+    let (success, output) = check_aof(&["--fix"], &path);
+    assert!(success);
+    assert!(output.contains(&format!("discard 16 bytes after offset {}", VALID.len())));
+    assert_eq!(std::fs::read(&path).unwrap(), VALID);
+    assert!(check_aof(&[], &path).0);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn check_aof_reports_parse_errors() {
+    let mut data = VALID.to_vec();
+    data.extend_from_slice(b"*1\r\n#x\r\n");
+    let path = write_aof("invalid", &data);
+
+    let (success, output) = check_aof(&[], &path);
+    assert!(!success);
+    assert!(output.contains(&format!(
+        "InvalidData in the command at offset {} (at offset {})",
+        VALID.len(),
+        VALID.len() + 5
+    )));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn check_aof_fixes_invalid_command_only_when_forced() {
+    let mut data = VALID.to_vec();
+    data.extend_from_slice(b"*1\r\n#x\r\n*1\r\n$4\r\nPING\r\n");
+    let path = write_aof("invalid_fix", &data);
+
+    // stdin is closed, so the confirmation is declined
+    let (success, output) = check_aof(&["--fix"], &path);
+    assert!(!success);
+    assert!(output.contains(&format!("discard 22 bytes after offset {}", VALID.len())));
+    assert!(output.contains("not truncating"));
+    assert_eq!(std::fs::read(&path).unwrap(), data);
+
+    let (success, _) = check_aof(&["--fix", "--force"], &path);
+    assert!(success);
+    assert_eq!(std::fs::read(&path).unwrap(), VALID);
+
+    std::fs::remove_file(path).unwrap();
+}